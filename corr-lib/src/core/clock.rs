@@ -0,0 +1,420 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, SystemTime};
+use async_trait::async_trait;
+use futures::task::ArcWake;
+use tokio::sync::oneshot;
+use tokio::task::{AbortHandle, JoinHandle};
+
+#[async_trait]
+pub trait Clock:Send+Sync{
+    fn now(&self)->SystemTime;
+    async fn sleep(&self,duration:Duration);
+    //Registers work the clock has to wait for before moving time, real time moves on its own
+    fn work(&self)->Work{
+        Work(Option::None)
+    }
+}
+#[derive(Debug)]
+pub struct RealClock;
+#[async_trait]
+impl Clock for RealClock{
+    fn now(&self)->SystemTime{
+        SystemTime::now()
+    }
+    async fn sleep(&self,duration:Duration){
+        tokio::time::sleep(duration).await
+    }
+}
+#[derive(Debug)]
+struct Unit{
+    working:bool,
+    woken:bool,
+    spawned:bool,
+    finished:bool,
+    task:Option<AbortHandle>
+}
+#[derive(Debug)]
+struct Timeline{
+    now:SystemTime,
+    sleepers:BTreeMap<(SystemTime,u64),oneshot::Sender<()>>,
+    units:HashMap<u64,Unit>,
+    working:usize,
+    waking:usize,
+    next_id:u64
+}
+impl Timeline{
+    //Moves time forward to given instant and hands back everyone due by then, time stays put until they are woken
+    fn advance_to(&mut self,instant:SystemTime)->Vec<oneshot::Sender<()>>{
+        if instant > self.now {
+            self.now = instant;
+        }
+        let pending = self.sleepers.split_off(&(self.now,u64::MAX));
+        let due:Vec<_> = std::mem::replace(&mut self.sleepers,pending).into_values().collect();
+        self.waking += due.len();
+        due
+    }
+    //Marks a unit of work runnable
+    fn rouse(&mut self,id:u64){
+        if let Some(unit) = self.units.get_mut(&id) {
+            unit.woken = true;
+            if !unit.working {
+                unit.working = true;
+                self.working += 1;
+            }
+        }
+    }
+    fn release(&mut self,id:u64){
+        if let Some(unit) = self.units.remove(&id) {
+            if unit.working {
+                self.working -= 1;
+            }
+        }
+    }
+}
+//Wakes sleepers outside the lock, their wakers take it again to mark their work runnable
+fn wake(timeline:&Mutex<Timeline>,due:Vec<oneshot::Sender<()>>){
+    let count = due.len();
+    for sleeper in due {
+        let _ = sleeper.send(());
+    }
+    timeline.lock().unwrap().waking -= count;
+}
+//Moves time to the earliest deadline for as long as no registered work is runnable
+fn settle(timeline:&Mutex<Timeline>){
+    loop {
+        let due = {
+            let mut timeline = timeline.lock().unwrap();
+            if timeline.working > 0 || timeline.waking > 0 {
+                return;
+            }
+            match timeline.sleepers.keys().next() {
+                Some(&(deadline,_))=>timeline.advance_to(deadline),
+                None=>return
+            }
+        };
+        wake(timeline,due);
+    }
+}
+thread_local! {
+    //Whether work is being polled on this thread, and whether it got to wait on the clock while at it
+    static POLLING:Cell<Option<bool>> = const { Cell::new(Option::None) };
+}
+//Marks the work being polled as waiting on the clock, false outside of any work
+fn nap()->bool{
+    POLLING.with(|polling|{
+        let working = polling.get().is_some();
+        if working {
+            polling.set(Option::Some(true));
+        }
+        working
+    })
+}
+//Lets go of work that ran in its own task once the task is done, so whoever joins it is woken before time moves
+fn reap(timeline:Arc<Mutex<Timeline>>,id:u64,task:AbortHandle){
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime)=>{
+            runtime.spawn(async move {
+                while !task.is_finished() {
+                    tokio::task::yield_now().await;
+                }
+                timeline.lock().unwrap().release(id);
+                settle(&timeline);
+            });
+        },
+        Err(_)=>{
+            timeline.lock().unwrap().release(id);
+        }
+    }
+}
+//Time only moves when advanced, sleepers wait for their own deadline and once every
+//registered unit of work waits on the clock, time moves to the earliest pending deadline
+#[derive(Debug, Clone)]
+pub struct VirtualClock{
+    timeline:Arc<Mutex<Timeline>>
+}
+impl VirtualClock{
+    pub fn new()->Self{
+        VirtualClock::starting_at(SystemTime::now())
+    }
+    pub fn starting_at(start:SystemTime)->Self{
+        VirtualClock{
+            timeline:Arc::new(Mutex::new(Timeline{
+                now:start,
+                sleepers:BTreeMap::new(),
+                units:HashMap::new(),
+                working:0,
+                waking:0,
+                next_id:0
+            }))
+        }
+    }
+    pub fn advance(&self,duration:Duration){
+        let due = {
+            let mut timeline = self.timeline.lock().unwrap();
+            let instant = timeline.now + duration;
+            timeline.advance_to(instant)
+        };
+        wake(&self.timeline,due);
+        settle(&self.timeline);
+    }
+}
+impl Default for VirtualClock{
+    fn default()->Self{
+        VirtualClock::new()
+    }
+}
+//A sleep on the timeline, forgets its deadline when dropped before it was due
+struct Nap{
+    timeline:Arc<Mutex<Timeline>>,
+    key:(SystemTime,u64),
+    woken:oneshot::Receiver<()>
+}
+impl Future for Nap{
+    type Output = ();
+    fn poll(mut self:Pin<&mut Self>,cx:&mut Context<'_>)->Poll<()>{
+        match Pin::new(&mut self.woken).poll(cx) {
+            Poll::Ready(_)=>Poll::Ready(()),
+            Poll::Pending=>{
+                //Sleeps outside of registered work never hold time back
+                if !nap() {
+                    settle(&self.timeline);
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+impl Drop for Nap{
+    fn drop(&mut self){
+        let sleeper = self.timeline.lock().unwrap().sleepers.remove(&self.key);
+        drop(sleeper);
+    }
+}
+#[async_trait]
+impl Clock for VirtualClock{
+    fn now(&self)->SystemTime{
+        self.timeline.lock().unwrap().now
+    }
+    async fn sleep(&self,duration:Duration){
+        if duration.is_zero() {
+            return tokio::task::yield_now().await;
+        }
+        let (waker,woken) = oneshot::channel();
+        let key = {
+            let mut timeline = self.timeline.lock().unwrap();
+            let key = (timeline.now + duration,timeline.next_id);
+            timeline.next_id += 1;
+            timeline.sleepers.insert(key,waker);
+            key
+        };
+        Nap{timeline:self.timeline.clone(),key,woken}.await
+    }
+    fn work(&self)->Work{
+        let mut timeline = self.timeline.lock().unwrap();
+        let id = timeline.next_id;
+        timeline.next_id += 1;
+        timeline.units.insert(id,Unit{working:true,woken:false,spawned:false,finished:false,task:Option::None});
+        timeline.working += 1;
+        Work(Option::Some((self.timeline.clone(),id)))
+    }
+}
+//Work registered with a virtual clock, runnable from creation until it waits on the clock.
+//Anything else it waits on, like a response, holds virtual time back until it is woken again
+pub struct Work(Option<(Arc<Mutex<Timeline>>,u64)>);
+impl Work{
+    pub fn run<F:Future>(self,future:F)->Working<F>{
+        Working{work:self,future:Box::pin(future)}
+    }
+    //Runs the work in a task of its own, it holds time back until the task has finished
+    pub fn spawn<F>(self,future:F)->JoinHandle<F::Output> where F:Future+Send+'static,F::Output:Send+'static{
+        let registered = self.0.clone();
+        if let Some((timeline,id)) = &registered {
+            if let Some(unit) = timeline.lock().unwrap().units.get_mut(id) {
+                unit.spawned = true;
+            }
+        }
+        let handle = tokio::spawn(self.run(future));
+        if let Some((timeline,id)) = registered {
+            let finished = {
+                let mut locked = timeline.lock().unwrap();
+                locked.units.get_mut(&id).map(|unit|{
+                    unit.task = Option::Some(handle.abort_handle());
+                    unit.finished
+                })
+            };
+            if finished == Option::Some(true) {
+                reap(timeline,id,handle.abort_handle());
+            }
+        }
+        handle
+    }
+}
+impl Drop for Work{
+    fn drop(&mut self){
+        if let Some((timeline,id)) = &self.0 {
+            let task = {
+                let mut locked = timeline.lock().unwrap();
+                match locked.units.get_mut(id) {
+                    Some(unit) if unit.spawned=>{
+                        unit.finished = true;
+                        Option::Some(unit.task.clone())
+                    },
+                    _=>{
+                        locked.release(*id);
+                        Option::None
+                    }
+                }
+            };
+            match task {
+                Some(Some(task))=>reap(timeline.clone(),*id,task),
+                Some(None)=>{},
+                None=>settle(timeline)
+            }
+        }
+    }
+}
+//Marks its work runnable before passing the wake up on
+struct Rouser{
+    timeline:Arc<Mutex<Timeline>>,
+    id:u64,
+    waker:Waker
+}
+impl ArcWake for Rouser{
+    fn wake_by_ref(arc_self:&Arc<Self>){
+        arc_self.timeline.lock().unwrap().rouse(arc_self.id);
+        arc_self.waker.wake_by_ref();
+    }
+}
+pub struct Working<F:Future>{
+    work:Work,
+    future:Pin<Box<F>>
+}
+impl<F:Future> Future for Working<F>{
+    type Output = F::Output;
+    fn poll(mut self:Pin<&mut Self>,cx:&mut Context<'_>)->Poll<F::Output>{
+        let this = &mut *self;
+        let (timeline,id) = match &this.work.0 {
+            Some((timeline,id))=>(timeline.clone(),*id),
+            None=>return this.future.as_mut().poll(cx)
+        };
+        {
+            let mut locked = timeline.lock().unwrap();
+            locked.rouse(id);
+            if let Some(unit) = locked.units.get_mut(&id) {
+                unit.woken = false;
+            }
+        }
+        let waker = futures::task::waker(Arc::new(Rouser{timeline:timeline.clone(),id,waker:cx.waker().clone()}));
+        let outer = POLLING.with(|current|current.replace(Option::Some(false)));
+        let polled = this.future.as_mut().poll(&mut Context::from_waker(&waker));
+        let idle = POLLING.with(|current|current.replace(outer)) == Option::Some(true);
+        if polled.is_pending() && idle {
+            {
+                let mut locked = timeline.lock().unwrap();
+                let napping = locked.units.get_mut(&id).filter(|unit|unit.working && !unit.woken).map(|unit|unit.working = false).is_some();
+                if napping {
+                    locked.working -= 1;
+                }
+            }
+            settle(&timeline);
+            nap();
+        }
+        polled
+    }
+}
+//Waits on other work without holding virtual time back, the work waited on holds it instead
+pub fn waiting<F:Future>(future:F)->Waiting<F>{
+    Waiting{future:Box::pin(future)}
+}
+pub struct Waiting<F:Future>{
+    future:Pin<Box<F>>
+}
+impl<F:Future> Future for Waiting<F>{
+    type Output = F::Output;
+    fn poll(mut self:Pin<&mut Self>,cx:&mut Context<'_>)->Poll<F::Output>{
+        let polled = self.future.as_mut().poll(cx);
+        if polled.is_pending() {
+            nap();
+        }
+        polled
+    }
+}
+#[cfg(test)]
+mod tests{
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::core::clock::{waiting, Clock, VirtualClock};
+
+    #[tokio::test]
+    async fn should_advance_virtual_clock_on_sleep(){
+        let clock = VirtualClock::starting_at(UNIX_EPOCH);
+        clock.sleep(Duration::from_secs(60)).await;
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(clock.now(),UNIX_EPOCH + Duration::from_millis(60500));
+    }
+    #[tokio::test]
+    async fn should_wake_concurrent_sleepers_at_their_own_deadline(){
+        let clock = VirtualClock::starting_at(UNIX_EPOCH);
+        let sleeper = |secs:u64|{
+            let clock = clock.clone();
+            clock.work().run(async move {
+                clock.sleep(Duration::from_secs(secs)).await;
+                clock.now()
+            })
+        };
+        let woken = futures::future::join_all(vec![sleeper(1),sleeper(1),sleeper(1),sleeper(3)]).await;
+        assert_eq!(woken,vec![UNIX_EPOCH + Duration::from_secs(1),UNIX_EPOCH + Duration::from_secs(1),UNIX_EPOCH + Duration::from_secs(1),UNIX_EPOCH + Duration::from_secs(3)]);
+        assert_eq!(clock.now(),UNIX_EPOCH + Duration::from_secs(3));
+    }
+    #[tokio::test]
+    async fn should_forget_deadline_of_dropped_sleep(){
+        let clock = VirtualClock::starting_at(UNIX_EPOCH);
+        clock.work().run(async {
+            tokio::select! {
+                _=clock.sleep(Duration::from_secs(60))=>{},
+                _=clock.sleep(Duration::from_secs(1))=>{}
+            }
+            clock.sleep(Duration::from_secs(2)).await;
+        }).await;
+        assert_eq!(clock.now(),UNIX_EPOCH + Duration::from_secs(3));
+    }
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn should_hold_virtual_time_while_work_waits_on_anything_else(){
+        let clock = VirtualClock::starting_at(UNIX_EPOCH);
+        let busy = clock.clone();
+        let worker = clock.work().spawn(async move {
+            let before = busy.now();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            busy.now().duration_since(before).unwrap()
+        });
+        let sleeper = clock.clone();
+        let woken = clock.work().spawn(async move {
+            sleeper.sleep(Duration::from_secs(10)).await;
+            sleeper.now()
+        });
+        assert_eq!(worker.await.unwrap(),Duration::from_secs(0));
+        assert_eq!(woken.await.unwrap(),UNIX_EPOCH + Duration::from_secs(10));
+    }
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn should_move_time_while_work_waits_on_other_work(){
+        let clock = VirtualClock::starting_at(UNIX_EPOCH);
+        let parent = clock.clone();
+        let finished = clock.work().run(async move {
+            let children:Vec<_> = (1..=3).map(|secs|{
+                let child = parent.clone();
+                parent.work().spawn(async move {
+                    child.sleep(Duration::from_secs(secs)).await;
+                    child.now()
+                })
+            }).collect();
+            let woken:Vec<_> = waiting(futures::future::join_all(children)).await.into_iter().map(|woken|woken.unwrap()).collect();
+            parent.sleep(Duration::from_secs(1)).await;
+            (woken,parent.now())
+        }).await;
+        assert_eq!(finished,(vec![UNIX_EPOCH + Duration::from_secs(1),UNIX_EPOCH + Duration::from_secs(2),UNIX_EPOCH + Duration::from_secs(3)],UNIX_EPOCH + Duration::from_secs(4)));
+    }
+}
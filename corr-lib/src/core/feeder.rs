@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use rand::Rng;
+use tokio::sync::RwLock;
+use anyhow::{bail, Result};
+use crate::core::Value;
+use crate::core::runtime::Context;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeedStrategy{
+    Sequential,
+    Random,
+    Unique,
+    PerVu
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeederDeclaration{
+    pub variable:String,
+    pub path:String,
+    pub strategy:FeedStrategy
+}
+#[derive(Debug)]
+pub struct Feeder{
+    records:Vec<Value>,
+    strategy:FeedStrategy,
+    cursor:AtomicUsize
+}
+impl Feeder{
+    pub fn new(records:Vec<Value>,strategy:FeedStrategy)->Self{
+        Feeder{
+            records,
+            strategy,
+            cursor:AtomicUsize::new(0)
+        }
+    }
+    //The whole file is read and parsed up front and its records stay in memory for the run,
+    //so a feeder takes a few times the size of its file
+    pub async fn load(path:&str,strategy:FeedStrategy)->Result<Self>{
+        let content = tokio::fs::read_to_string(path).await?;
+        let records = if path.to_lowercase().ends_with(".json") {
+            match Value::from_json_value(serde_json::from_str(content.as_str())?) {
+                Value::Array(records)=>records,
+                _=>bail!("Feeder file {} should contain an array of records",path)
+            }
+        } else {
+            let mut reader = csv::Reader::from_reader(content.as_bytes());
+            let headers = reader.headers()?.clone();
+            let mut records = vec![];
+            for row in reader.records() {
+                let row = row?;
+                let mut record = HashMap::new();
+                for (header,field) in headers.iter().zip(row.iter()) {
+                    record.insert(header.to_string(),Value::String(field.to_string()));
+                }
+                records.push(Value::Map(record));
+            }
+            records
+        };
+        if records.is_empty() {
+            bail!("Feeder file {} has no records",path)
+        }
+        Ok(Feeder::new(records,strategy))
+    }
+    pub fn next(&self,vu:Option<u128>,context:&Context)->Option<Value>{
+        let len = self.records.len();
+        match (self.strategy,vu) {
+            (FeedStrategy::Random,_)=>{
+                self.records.get(context.with_rng(|rng|rng.gen_range(0..len))).cloned()
+            },
+            (FeedStrategy::Unique,_)=>{
+                self.records.get(self.cursor.fetch_add(1,Ordering::SeqCst)).cloned()
+            },
+            (FeedStrategy::PerVu,Some(vu))=>{
+                self.records.get((vu % len as u128) as usize).cloned()
+            },
+            _=>{
+                self.records.get(self.cursor.fetch_add(1,Ordering::SeqCst) % len).cloned()
+            }
+        }
+    }
+}
+//Feeders of the workload with the variable each of them defines
+type Declared = Arc<RwLock<Vec<(String,Arc<Feeder>)>>>;
+#[derive(Clone)]
+pub struct FeederStore{
+    feeders:Arc<RwLock<HashMap<String,Arc<Feeder>>>>,
+    declared:Declared
+}
+impl FeederStore{
+    pub fn new()->Self{
+        FeederStore{
+            feeders:Arc::new(RwLock::new(HashMap::new())),
+            declared:Arc::new(RwLock::new(vec![]))
+        }
+    }
+    //Files are read without holding the store, when two loads of one file race the first one stored wins
+    pub async fn get_or_load(&self,path:String,strategy:FeedStrategy)->Result<Arc<Feeder>>{
+        let key = format!("{}:{:?}",path,strategy);
+        if let Some(feeder) = self.feeders.read().await.get(&key) {
+            return Ok(feeder.clone());
+        }
+        let feeder = Arc::new(Feeder::load(path.as_str(),strategy).await?);
+        Ok(self.feeders.write().await.entry(key).or_insert(feeder).clone())
+    }
+    pub async fn declare(&self,declaration:&FeederDeclaration)->Result<()>{
+        let feeder = self.get_or_load(declaration.path.clone(),declaration.strategy).await?;
+        self.declared.write().await.push((declaration.variable.clone(),feeder));
+        Ok(())
+    }
+    //Defines next record of every declared feeder, false when any of them is exhausted
+    pub async fn feed(&self,context:&Context)->bool{
+        let vu = current_vu(context).await;
+        for (variable,feeder) in self.declared.read().await.iter() {
+            if let Some(record) = feeder.next(vu,context) {
+                context.define(variable.clone(),record).await;
+            } else {
+                return false;
+            }
+        }
+        true
+    }
+}
+impl Default for FeederStore{
+    fn default()->Self{
+        FeederStore::new()
+    }
+}
+pub async fn current_vu(context:&Context)->Option<u128>{
+    match context.get_var_from_store("__VU".to_string()).await {
+        Some(Value::PositiveInteger(vu))=>Option::Some(vu),
+        _=>Option::None
+    }
+}
+#[cfg(test)]
+mod tests{
+    use std::sync::{Arc, Mutex};
+    use crate::core::feeder::{Feeder, FeederStore, FeedStrategy};
+    use crate::core::runtime::Context;
+    use crate::core::Value;
+
+    #[test]
+    fn should_stop_unique_feeder_when_exhausted(){
+        let context = Context::mock(vec![],Arc::new(Mutex::new(vec![])));
+        let feeder = Feeder::new(vec![Value::PositiveInteger(1),Value::PositiveInteger(2)],FeedStrategy::Unique);
+        assert_eq!(feeder.next(Option::None,&context),Option::Some(Value::PositiveInteger(1)));
+        assert_eq!(feeder.next(Option::None,&context),Option::Some(Value::PositiveInteger(2)));
+        assert_eq!(feeder.next(Option::None,&context),Option::None);
+    }
+    #[test]
+    fn should_wrap_sequential_feeder_and_pin_per_vu_feeder(){
+        let context = Context::mock(vec![],Arc::new(Mutex::new(vec![])));
+        let feeder = Feeder::new(vec![Value::PositiveInteger(1),Value::PositiveInteger(2)],FeedStrategy::Sequential);
+        feeder.next(Option::None,&context);
+        feeder.next(Option::None,&context);
+        assert_eq!(feeder.next(Option::None,&context),Option::Some(Value::PositiveInteger(1)));
+        let feeder = Feeder::new(vec![Value::PositiveInteger(1),Value::PositiveInteger(2)],FeedStrategy::PerVu);
+        assert_eq!(feeder.next(Option::Some(3),&context),Option::Some(Value::PositiveInteger(2)));
+        assert_eq!(feeder.next(Option::Some(3),&context),Option::Some(Value::PositiveInteger(2)));
+    }
+    #[tokio::test]
+    async fn should_share_one_feeder_between_concurrent_loads(){
+        let path = std::env::temp_dir().join(format!("corr-feeder-{}.csv",std::process::id()));
+        std::fs::write(&path,"user\nalice\nbob\n").unwrap();
+        let store = FeederStore::default();
+        let path = path.to_str().unwrap().to_string();
+        let (first,second) = tokio::join!(store.get_or_load(path.clone(),FeedStrategy::Unique),store.get_or_load(path.clone(),FeedStrategy::Unique));
+        std::fs::remove_file(&path).unwrap();
+        let first = first.unwrap();
+        assert!(Arc::ptr_eq(&first,&second.unwrap()));
+        assert!(Arc::ptr_eq(&first,&store.get_or_load(path,FeedStrategy::Unique).await.unwrap()));
+    }
+    #[test]
+    fn should_repeat_random_records_for_same_seed(){
+        let records:Vec<Value> = (0..100).map(Value::PositiveInteger).collect();
+        let feeder = Feeder::new(records,FeedStrategy::Random);
+        let draw = ||{
+            let mut context = Context::mock(vec![],Arc::new(Mutex::new(vec![])));
+            context.set_seed(42);
+            (0..10).map(|_|feeder.next(Option::None,&context)).collect::<Vec<Option<Value>>>()
+        };
+        assert_eq!(draw(),draw());
+    }
+}
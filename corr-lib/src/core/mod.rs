@@ -6,11 +6,15 @@ use std::sync::Arc;
 
 use core::str::FromStr;
 use tokio::sync::RwLock;
+use std::time::Duration;
+use rand::Rng;
 
 pub mod proto;
 pub mod runtime;
 pub mod parser;
 pub mod scrapper;
+pub mod feeder;
+pub mod clock;
 #[derive(Debug, Clone,Copy, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", content = "payload", rename_all = "camelCase")]
 pub enum DataType {
@@ -33,7 +37,8 @@ pub enum Value{
     Double(f64),
     Null,
     Array(Vec<Value>),
-    Map(HashMap<String,Value>)
+    Map(HashMap<String,Value>),
+    Duration(Duration)
 }
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", content = "payload", rename_all = "camelCase")]
@@ -525,6 +530,7 @@ impl Value {
             Value::Integer(val)=>serde_json::Value::from(val.clone() as i64),
             Value::PositiveInteger(val)=>serde_json::Value::from(val.clone() as u64),
             Value::Buffer(_val)=>panic!("Can't converted to jason value"),
+            Value::Duration(d)=>serde_json::Value::from(d.as_millis() as u64),
             Value::Null=>serde_json::Value::Null,
             Value::Map(hm)=>{
                 let mut new_hm = serde_json::Map::new();
@@ -547,6 +553,7 @@ impl Value {
             Value::Double(dbl)=>Option::Some(Number::Double(dbl.clone())),
             Value::PositiveInteger(lng)=>Option::Some(Number::PositiveInteger(lng.clone())),
             Value::Integer(lng)=>Option::Some(Number::Integer(lng.clone())),
+            Value::Duration(d)=>Option::Some(Number::PositiveInteger(d.as_millis())),
             Value::String(str)=>{
                 if let Ok(val) = str.parse::<u128>(){
                     Option::Some(Number::PositiveInteger(val))
@@ -561,6 +568,12 @@ impl Value {
             _=>Option::None
         }
     }
+    pub fn to_duration(&self)->Option<Duration>{
+        match self {
+            Value::Duration(d)=>Option::Some(*d),
+            _=>self.to_number().and_then(|num|num.as_usize()).map(|ms|Duration::from_millis(ms as u64))
+        }
+    }
     pub fn is_of_type(&self,data_type:DataType)->bool{
         match self {
             Value::Null=>true,
@@ -606,6 +619,7 @@ impl Value {
             Value::Integer(lng)=>format!("{}",lng),
             Value::Double(dbl)=>format!("{}",dbl),
             Value::Boolean(bln)=>format!("{}",bln),
+            Value::Duration(d)=>format!("{}ms",d.as_millis()),
             Value::Map(hm)=>{
                 let mut vec=vec![];
                 let mut sb= "".to_string();
@@ -648,6 +662,7 @@ impl Value {
             Value::Buffer(b)=>b.clone(),
             Value::Integer(lng)=>lng.to_le_bytes().iter().map(|b|b.clone()).collect(),
             Value::Double(dbl)=>dbl.to_le_bytes().iter().map(|b|b.clone()).collect(),
+            Value::Duration(d)=>d.as_millis().to_le_bytes().to_vec(),
             Value::Boolean(_bln)=>panic!("Cant Convert bool"),
             Value::Map(_hm)=>{ panic!("Cant Convert Object")
             },
@@ -677,6 +692,33 @@ impl Variable{
         }
     }
 }
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThinkTime{
+    Fixed(Duration),
+    Uniform(Duration,Duration),
+    Normal(Duration,Duration)
+}
+impl ThinkTime{
+    pub fn sample(&self,rng:&mut impl Rng)->Duration{
+        match self {
+            ThinkTime::Fixed(d)=>*d,
+            ThinkTime::Uniform(min,max)=>{
+                if max > min {
+                    Duration::from_millis(rng.gen_range(min.as_millis() as u64..=max.as_millis() as u64))
+                } else {
+                    *min
+                }
+            },
+            ThinkTime::Normal(mean,std_dev)=>{
+                //Box-Muller transform
+                let u1:f64 = 1.0 - rng.gen::<f64>();
+                let u2:f64 = rng.gen::<f64>();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                Duration::from_secs_f64((mean.as_secs_f64() + z * std_dev.as_secs_f64()).max(0.0))
+            }
+        }
+    }
+}
 pub fn convert(name:String,value:String,data_type:DataType)->Option<VariableValue>{
     match data_type {
         DataType::String=>Option::Some(VariableValue{name,value:Value::String(value)}),
@@ -1,11 +1,14 @@
 use crate::parser::{Parsable, ParseResult, ws};
-use crate::core::{Value, Variable, DataType};
-use nom::combinator::{map, opt, value};
-use nom::sequence::{tuple, preceded, delimited};
+use crate::core::{Value, Variable, DataType, ThinkTime};
+use crate::core::feeder::{FeedStrategy, FeederDeclaration};
+use crate::parser::identifier_part;
+use nom::combinator::{map, opt, value, not};
+use nom::sequence::{tuple, preceded, delimited, separated_pair, terminated};
 use nom::bytes::complete::{tag, escaped, is_not};
-use nom::character::complete::{char, anychar, digit1};
+use nom::character::complete::{char, anychar, digit1, u64, alphanumeric1};
 use nom::branch::alt;
 use crate::template::VariableReferenceName;
+use std::time::Duration;
 
 pub fn double<'a>(input: &'a str) -> ParseResult<'a, f64> {
     let mut num = tuple((opt(tag("-")),digit1,tag("."),digit1));
@@ -64,11 +67,25 @@ pub fn string<'a>(input: &'a str) -> ParseResult<'a, String> {
         |s:Option<&str>| s.map(|s| s.to_string()).unwrap_or("".to_string()),
     )(input)
 }
+pub fn duration<'a>(input: &'a str) -> ParseResult<'a, Duration> {
+    map(terminated(tuple((u64,alt((tag("ms"),tag("s"),tag("m"),tag("h"))))),not(alphanumeric1)),|(amount,unit)|{
+        match unit {
+            "ms"=>Duration::from_millis(amount),
+            "s"=>Duration::from_secs(amount),
+            "m"=>Duration::from_secs(amount * 60),
+            _=>Duration::from_secs(amount * 3600)
+        }
+    })(input)
+}
+pub fn duration_or<'a>(unit:fn(u64)->Duration)->impl Fn(&'a str) -> ParseResult<'a, Duration> {
+    move |input| alt((duration,map(u64,unit)))(input)
+}
 impl Parsable for Value {
     fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
         alt((
             map(tag("null"),|_|Value::Null),
             map(boolean,|val|Value::Boolean(val)),
+            map(duration,Value::Duration),
             map(double,|val|Value::Double(val)),
             map(string,|val|Value::String(val)),
             map(positive_integer,|val|Value::PositiveInteger(val)),
@@ -94,12 +111,79 @@ impl Parsable for DataType {
             ))(input)
     }
 }
+impl Parsable for ThinkTime {
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        alt((
+            map(preceded(ws(tag("fixed")),delimited(ws(char('(')),ws(duration_or(Duration::from_millis)),ws(char(')')))),ThinkTime::Fixed),
+            map(preceded(ws(tag("uniform")),delimited(ws(char('(')),separated_pair(ws(duration_or(Duration::from_millis)),char(','),ws(duration_or(Duration::from_millis))),ws(char(')')))),|(min,max)|ThinkTime::Uniform(min,max)),
+            map(preceded(ws(tag("normal")),delimited(ws(char('(')),separated_pair(ws(duration_or(Duration::from_millis)),char(','),ws(duration_or(Duration::from_millis))),ws(char(')')))),|(mean,std_dev)|ThinkTime::Normal(mean,std_dev)),
+            map(ws(duration_or(Duration::from_millis)),ThinkTime::Fixed)
+        ))(input)
+    }
+}
+impl Parsable for FeedStrategy {
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        alt((
+            value(FeedStrategy::Sequential,tag("sequential")),
+            value(FeedStrategy::Random,tag("random")),
+            value(FeedStrategy::Unique,tag("unique")),
+            value(FeedStrategy::PerVu,tag("per-vu"))
+        ))(input)
+    }
+}
+impl Parsable for FeederDeclaration {
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        map(tuple((ws(identifier_part),ws(tag("from")),ws(string),opt(ws(FeedStrategy::parser)))),|(variable,_,path,strategy)|FeederDeclaration{
+            variable:variable.to_string(),
+            path,
+            strategy:strategy.unwrap_or(FeedStrategy::Sequential)
+        })(input)
+    }
+}
 #[cfg(test)]
 mod tests{
     use crate::parser::Parsable;
     use crate::parser::util::assert_if;
-    use crate::core::{Value};
+    use crate::core::{Value, ThinkTime};
+    use crate::core::feeder::{FeedStrategy, FeederDeclaration};
+    use std::time::Duration;
 
+    #[tokio::test]
+    async fn should_parse_duration_values(){
+        let j= r#"250ms"#;
+        assert_if(j
+                  ,Value::parser(j)
+                  ,Value::Duration(Duration::from_millis(250)));
+        let j= r#"5m"#;
+        assert_if(j
+                  ,Value::parser(j)
+                  ,Value::Duration(Duration::from_secs(300)));
+        let j= r#"1h"#;
+        assert_if(j
+                  ,Value::parser(j)
+                  ,Value::Duration(Duration::from_secs(3600)))
+
+    }
+    #[tokio::test]
+    async fn should_parse_think_time(){
+        let j= r#"uniform(500, 1500)"#;
+        assert_if(j
+                  ,ThinkTime::parser(j)
+                  ,ThinkTime::Uniform(Duration::from_millis(500),Duration::from_millis(1500)));
+        let j= r#"2s"#;
+        assert_if(j
+                  ,ThinkTime::parser(j)
+                  ,ThinkTime::Fixed(Duration::from_secs(2)))
+
+    }
+    #[tokio::test]
+    async fn should_parse_feeder_declaration(){
+        let j= r#"user from "users.csv" per-vu"#;
+        assert_if(j
+                  ,FeederDeclaration::parser(j)
+                  ,FeederDeclaration{variable:"user".to_string(),path:"users.csv".to_string(),strategy:FeedStrategy::PerVu})
+
+    }
     #[tokio::test]
     async fn should_parse_null_value(){
         let j= r#"null"#;
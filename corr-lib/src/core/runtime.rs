@@ -1,5 +1,6 @@
 use crate::core::{Value, DataType, Variable, VariableValue, Number};
 use std::sync::{Arc};
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::RwLock;
 use futures::lock::Mutex;
 use std::collections::HashMap;
@@ -15,11 +16,18 @@ use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 use crate::core::scrapper::none::NoneScraper;
 use crate::core::scrapper::Scrapper;
+use crate::core::feeder::FeederStore;
 use crate::journey::Journey;
 use crate::template::rest::RestVerb;
 use crate::template::VariableReferenceName;
 use anyhow::Result;
 use tokio::net::TcpStream;
+use std::time::{Duration, SystemTime};
+use crate::core::clock::{Clock, RealClock};
+use crate::journey::step::rest::client::{HttpClients, HttpConfig};
+use crate::journey::step::rest::timing::Timings;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
 fn min(values: &[f64]) -> f64 {
     values
@@ -141,7 +149,10 @@ impl HeapObject{
 #[derive(Clone)]
 pub struct RestStatsStore{
     parent:Option<Box<RestStatsStore>>,
-    samples:Arc<Mutex<Vec<(RestVerb,String,u128)>>>
+    samples:Arc<Mutex<Vec<(RestVerb,String,u128)>>>,
+    timeouts:Arc<Mutex<Vec<(RestVerb,String)>>>,
+    retries:Arc<Mutex<Vec<(RestVerb,String)>>>,
+    timings:Arc<Mutex<Vec<(RestVerb,String,Timings)>>>
 }
 #[derive(Clone)]
 pub struct TransactionsStatsStore{
@@ -175,6 +186,9 @@ pub fn break_on(path:String,chr:char)->Option<(String,String)>{
         Option::None
     }
 }
+pub fn derive_seed(seed:u64,vu:u64,iteration:u64)->u64{
+    seed ^ vu.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ iteration.wrapping_add(1).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+}
 
 #[async_recursion]
 pub async fn contains_reference_in(path:String,heap_object_ref:Arc<RwLock<HeapObject>>)->bool{
@@ -248,7 +262,10 @@ impl RestStatsStore{
     pub fn new()->Self{
         Self{
             parent:Option::None,
-            samples:Arc::new(Mutex::new(vec![]))
+            samples:Arc::new(Mutex::new(vec![])),
+            timeouts:Arc::new(Mutex::new(vec![])),
+            retries:Arc::new(Mutex::new(vec![])),
+            timings:Arc::new(Mutex::new(vec![]))
         }
     }
     pub async fn print_stats(&self){
@@ -265,15 +282,39 @@ impl RestStatsStore{
             println!("MAX: {}", max(&samples));
             println!("Average: {}", mean(&samples));
         }
+        let timeouts = self.timeouts.lock().await.len();
+        let retries = self.retries.lock().await.len();
+        if timeouts > 0 || retries > 0 {
+            println!("Timeouts: {}", timeouts);
+            println!("Retries: {}", retries);
+        }
+        let timings = self.timings.lock().await;
+        let mut phases = group_by(timings.iter().flat_map(|(_v,_u,t)|t.series()).map(|(series,duration)|(series.to_string(),duration.as_secs_f64()*1000.0)).collect());
+        phases.sort_by(|(a,_),(b,_)|a.cmp(b));
+        for (series,durations) in phases {
+            println!("Average {}: {:.2}", series, mean(&durations));
+        }
     }
     pub async fn get_stats(&self)->Vec<(RestVerb,String,u128)>{
         let samples = self.samples.lock().await;
         (&(*samples)).iter().map(|(v,u,t)|(v.clone(),u.clone(),t.clone())).collect()
     }
+    pub async fn get_timeouts(&self)->Vec<(RestVerb,String)>{
+        self.timeouts.lock().await.clone()
+    }
+    pub async fn get_retries(&self)->Vec<(RestVerb,String)>{
+        self.retries.lock().await.clone()
+    }
+    pub async fn get_timings(&self)->Vec<(RestVerb,String,Timings)>{
+        self.timings.lock().await.clone()
+    }
     pub async fn from(rs:&RestStatsStore)->Self{
         return Self{
             parent:Option::Some(Box::new(rs.clone())),
-            samples:Arc::new(Mutex::new(rs.samples.lock().await.clone()))
+            samples:Arc::new(Mutex::new(rs.samples.lock().await.clone())),
+            timeouts:Arc::new(Mutex::new(rs.timeouts.lock().await.clone())),
+            retries:Arc::new(Mutex::new(rs.retries.lock().await.clone())),
+            timings:Arc::new(Mutex::new(rs.timings.lock().await.clone()))
         }
     }
 
@@ -285,6 +326,27 @@ impl RestStatsStore{
             p.push_stat(stat).await;
         }
     }
+    #[async_recursion]
+    pub async fn push_timeout(&self,request:(RestVerb,String)){
+        self.timeouts.lock().await.push(request.clone());
+        if let Some(p)=&self.parent{
+            p.push_timeout(request).await;
+        }
+    }
+    #[async_recursion]
+    pub async fn push_retry(&self,request:(RestVerb,String)){
+        self.retries.lock().await.push(request.clone());
+        if let Some(p)=&self.parent{
+            p.push_retry(request).await;
+        }
+    }
+    #[async_recursion]
+    pub async fn push_timings(&self,timings:(RestVerb,String,Timings)){
+        self.timings.lock().await.push(timings.clone());
+        if let Some(p)=&self.parent{
+            p.push_timings(timings).await;
+        }
+    }
 
 }
 pub fn group_by(samples:Vec<(String,f64)>) -> Vec<(String, Vec<f64>)>
@@ -664,7 +726,14 @@ pub struct Context{
     pub websocket_connection_store:WebsocketConnectionStore,
     pub rest_stats_store:RestStatsStore,
     pub tr_stats_store:TransactionsStatsStore,
+    pub feeder_store:FeederStore,
+    pub http:HttpClients,
     pub fallback:bool,
+    pub clock:Arc<dyn Clock>,
+    pub started:SystemTime,
+    pub failed:Arc<AtomicBool>,
+    pub seed:u64,
+    rng:Arc<std::sync::Mutex<StdRng>>,
     pub sender:Option<Arc<Mutex<tokio::sync::mpsc::UnboundedSender<i32>>>>
 }
 impl Context {
@@ -685,6 +754,36 @@ impl Context {
             let _ = (*vl).send(message);
         }
     }
+    pub fn mark_failed(&self){
+        self.failed.store(true,Ordering::SeqCst);
+    }
+    pub fn has_failed(&self)->bool{
+        self.failed.load(Ordering::SeqCst)
+    }
+    pub fn set_clock(&mut self,clock:Arc<dyn Clock>){
+        self.started = clock.now();
+        self.clock = clock;
+    }
+    //Pacing of a journey counts from the start of its iteration
+    pub fn start_iteration(&mut self){
+        self.started = self.clock.now();
+    }
+    pub fn elapsed(&self)->Duration{
+        self.clock.now().duration_since(self.started).unwrap_or(Duration::from_millis(0))
+    }
+    pub fn set_seed(&mut self,seed:u64){
+        self.seed = seed;
+        self.rng = Arc::new(std::sync::Mutex::new(StdRng::seed_from_u64(seed)));
+    }
+    //Reseeds the random generator from the run seed and current __VU and __ITER
+    pub async fn reseed(&self){
+        let vu = self.get_var_from_store("__VU".to_string()).await.and_then(|v|v.to_number()).and_then(|n|n.as_usize()).unwrap_or(0) as u64;
+        let iter = self.get_var_from_store("__ITER".to_string()).await.and_then(|v|v.to_number()).and_then(|n|n.as_usize()).unwrap_or(0) as u64;
+        *self.rng.lock().unwrap() = StdRng::seed_from_u64(derive_seed(self.seed,vu,iter));
+    }
+    pub fn with_rng<T>(&self,generate:impl FnOnce(&mut StdRng)->T)->T{
+        generate(&mut self.rng.lock().unwrap())
+    }
     pub async fn get_var_from_store(&self,name:String)->Option<Value>{
         if let Some(var)=self.store.get(name.clone()).await{
             Option::Some(var.read().await.to_value().await)
@@ -709,12 +808,20 @@ impl Context {
             websocket_clients:WebSocketClientConnections::new(),
             rest_stats_store:context.rest_stats_store.clone(),
             tr_stats_store:context.tr_stats_store.clone(),
+            feeder_store:context.feeder_store.clone(),
+            http:context.http.clone(),
             global_store:context.global_store.clone(),
             store:ReferenceStore::new_from_references(context.store.references.clone()).await,
-            fallback:context.fallback
+            fallback:context.fallback,
+            clock:context.clock.clone(),
+            started:context.clock.now(),
+            failed:Arc::new(AtomicBool::new(false)),
+            seed:context.seed,
+            rng:Arc::new(std::sync::Mutex::new(StdRng::seed_from_u64(context.seed)))
         }
     }
-    pub fn new(user:Arc<Mutex<dyn Client>>,journeys:HashMap<String,Arc<Journey>>,scrapper:Arc<Box<dyn Scrapper>>,debug:bool)->Self{
+    pub fn new(user:Arc<Mutex<dyn Client>>,journeys:HashMap<String,Arc<Journey>>,scrapper:Arc<Box<dyn Scrapper>>,http:HttpClients,debug:bool)->Self{
+        let seed = rand::random();
         Context{
             debug,
             sender:Option::None,
@@ -727,9 +834,16 @@ impl Context {
             websocket_clients:WebSocketClientConnections::new(),
             rest_stats_store:RestStatsStore::new(),
             tr_stats_store:TransactionsStatsStore::new(),
+            feeder_store:FeederStore::new(),
+            http,
             global_store:ReferenceStore::new(),
             store:ReferenceStore::new(),
-            fallback:true
+            fallback:true,
+            clock:Arc::new(RealClock),
+            started:SystemTime::now(),
+            failed:Arc::new(AtomicBool::new(false)),
+            seed,
+            rng:Arc::new(std::sync::Mutex::new(StdRng::seed_from_u64(seed)))
         }
     }
     pub async fn define(&self,var:String,value:Value){
@@ -783,9 +897,16 @@ impl Context {
             websocket_clients:WebSocketClientConnections::from(&context.websocket_clients).await,
             rest_stats_store:RestStatsStore::from(&context.rest_stats_store).await,
             tr_stats_store:TransactionsStatsStore::from(&context.tr_stats_store).await,
+            feeder_store:context.feeder_store.clone(),
+            http:context.http.clone(),
             global_store:context.global_store.clone(),
             store:ReferenceStore::from(&context.store).await,
-            fallback:context.fallback
+            fallback:context.fallback,
+            clock:context.clock.clone(),
+            started:context.started,
+            failed:context.failed.clone(),
+            seed:context.seed,
+            rng:context.rng.clone()
         }
     }
     pub async fn from_without_fallback(context:&Context)->Self{
@@ -801,9 +922,16 @@ impl Context {
             websocket_clients:WebSocketClientConnections::from(&context.websocket_clients).await,
             rest_stats_store:RestStatsStore::from(&context.rest_stats_store).await,
             tr_stats_store:TransactionsStatsStore::from(&context.tr_stats_store).await,
+            feeder_store:context.feeder_store.clone(),
+            http:context.http.clone(),
             global_store:context.global_store.clone(),
             store:ReferenceStore::from(&context.store).await,
-            fallback:false
+            fallback:false,
+            clock:context.clock.clone(),
+            started:context.started,
+            failed:context.failed.clone(),
+            seed:context.seed,
+            rng:context.rng.clone()
         }
     }
     pub async fn delete(&self,path:String){
@@ -932,7 +1060,14 @@ pub struct MockClient {
 impl Context{
     pub fn mock(inputs:Vec<Input>,buffer:Arc<std::sync::Mutex<Vec<Output>>>)->Self{
         let user=Arc::new(futures::lock::Mutex::new(MockClient::new(inputs,buffer)));
-        Context::new(user,HashMap::new(),Arc::new(Box::new(NoneScraper{})),false)
+        //Every mock gets clients of its own so tests don't share pools, cookies or tokens
+        let http = HttpClients::new(HttpConfig::default()).expect("Unable to set up http clients");
+        Context::new(user,HashMap::new(),Arc::new(Box::new(NoneScraper{})),http,false)
+    }
+    pub fn mock_with_clock(inputs:Vec<Input>,buffer:Arc<std::sync::Mutex<Vec<Output>>>,clock:Arc<dyn Clock>)->Self{
+        let mut context = Context::mock(inputs,buffer);
+        context.set_clock(clock);
+        context
     }
 }
 
@@ -966,6 +1101,8 @@ pub mod tests{
     use std::sync::{Arc, Mutex};
     
     use crate::core::runtime::{Context, IO, break_on};
+    use crate::core::Value;
+    use rand::Rng;
 
 
 
@@ -1048,4 +1185,20 @@ pub mod tests{
         assert_eq!( buffer.lock().unwrap().get(1).unwrap().clone(),Output::new_tell_me("person.name".to_string(),DataType::String));
         assert_eq!( buffer.lock().unwrap().get(2).unwrap().clone(),Output::new_tell_me("person.name".to_string(),DataType::String));
     }
+    #[tokio::test]
+    async fn should_generate_same_random_values_for_same_seed_vu_and_iteration(){
+        let mut context = Context::mock(vec![],Arc::new(Mutex::new(vec![])));
+        context.set_seed(42);
+        context.define("__VU".to_string(),Value::PositiveInteger(3)).await;
+        context.define("__ITER".to_string(),Value::PositiveInteger(7)).await;
+        context.reseed().await;
+        let first:Vec<u64> = context.with_rng(|rng|(0..5).map(|_|rng.gen()).collect());
+        context.reseed().await;
+        let second:Vec<u64> = context.with_rng(|rng|(0..5).map(|_|rng.gen()).collect());
+        assert_eq!(first,second);
+        context.define("__ITER".to_string(),Value::PositiveInteger(8)).await;
+        context.reseed().await;
+        let third:Vec<u64> = context.with_rng(|rng|(0..5).map(|_|rng.gen()).collect());
+        assert_ne!(first,third);
+    }
 }
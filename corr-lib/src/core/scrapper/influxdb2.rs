@@ -5,11 +5,11 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use futures_util::stream;
 use influxdb2::Client;
 use influxdb2::models::DataPoint;
+use crate::core::clock::Clock;
 use crate::core::scrapper::{Metrics, Scrapper};
 use async_trait::async_trait;
 
 use tokio::sync::RwLock;
-use tokio::time::sleep;
 #[derive(Clone)]
 pub struct MDP{
     series:String,
@@ -24,7 +24,7 @@ pub struct InfluxDB2Scrapper{
 }
 #[async_trait]
 impl Scrapper for InfluxDB2Scrapper{
-    async fn start_metrics_loop(&self) {
+    async fn start_metrics_loop(&self,clock:Arc<dyn Clock>) {
         loop {
             let points_copy;
             {
@@ -47,7 +47,7 @@ impl Scrapper for InfluxDB2Scrapper{
                 cl.write(b.as_str(),stream::iter(pts)).await.unwrap();
             };
             tokio::spawn( task());
-            sleep(Duration::from_millis(500)).await;
+            clock.sleep(Duration::from_millis(500)).await;
         }
     }
 
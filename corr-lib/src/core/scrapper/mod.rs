@@ -3,6 +3,7 @@ pub mod none;
 
 use std::sync::Arc;
 use async_trait::async_trait;
+use crate::core::clock::Clock;
 use tokio::sync::RwLock;
 
 
@@ -25,7 +26,7 @@ impl Metrics{
 
 #[async_trait]
 pub trait Scrapper:Send+Sync{
-    async fn start_metrics_loop(&self);
+    async fn start_metrics_loop(&self,clock:Arc<dyn Clock>);
     async fn ingest(&self,series:&str,data:f64,tags:Vec<(String,String)>);
     async fn ingest_metric(&self,metrics:Arc<Metrics>,tag:(String,String));
 }
\ No newline at end of file
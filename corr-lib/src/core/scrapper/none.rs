@@ -1,15 +1,15 @@
 use std::sync::Arc;
 use std::time::Duration;
 use async_trait::async_trait;
-use tokio::time::sleep;
+use crate::core::clock::Clock;
 use crate::core::scrapper::{Metrics, Scrapper};
 
 pub struct NoneScraper;
 #[async_trait]
 impl Scrapper for NoneScraper{
-    async fn start_metrics_loop(&self) {
+    async fn start_metrics_loop(&self,clock:Arc<dyn Clock>) {
         loop {
-            sleep(Duration::from_secs(1)).await;
+            clock.sleep(Duration::from_secs(1)).await;
         }
     }
 
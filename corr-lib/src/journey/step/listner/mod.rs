@@ -44,7 +44,7 @@ async fn handle(
         {
             for stub in sls.stubs {
                 let context = Context::from_without_fallback(&context).await;
-                if stub.url.capture(&req.uri().to_string(), &context).await && req.method().to_string().to_lowercase().eq(&stub.method.as_str().to_lowercase()) {
+                if stub.url.capture(&req.uri().to_string(), &context).await && req.method().as_str().eq(stub.method.as_method().as_str()) {
                     let ct=req.headers().get(hyper::header::CONTENT_TYPE).map(|hv|hv.to_str().ok()).flatten().unwrap_or("application/json").to_lowercase();
                     let opt_bd = req.headers().get(hyper::header::CONTENT_TYPE).and_then(|ct| ct.to_str().ok()).and_then(|ct| multer::parse_boundary(ct).ok());
                     let (parts, body) = req.into_parts();
@@ -93,13 +93,16 @@ async fn handle(
                         step.execute(&context).await?;
                     }
 
-                    let resp = stub.response.body.evaluate(&context).await?;
                     let status: Option<u16> = stub.response.status.evaluate(&context).await?.parse();
                     ret_st = status.clone();
-                    ret = Option::Some(Response::builder()
-                        .status(StatusCode::from_u16(status.unwrap_or(200)).unwrap_or(StatusCode::OK))
-                        .header("Content-Type", "application/json")
-                        .body(Body::from(resp.to_string())));
+                    let builder = Response::builder()
+                        .status(StatusCode::from_u16(status.unwrap_or(200)).unwrap_or(StatusCode::OK));
+                    ret = Option::Some(if let Some(body) = &stub.response.body {
+                        let resp = body.evaluate(&context).await?;
+                        builder.header("Content-Type", "application/json").body(Body::from(resp.to_string()))
+                    } else {
+                        builder.body(Body::empty())
+                    });
                     break;
                 }
             }
@@ -175,10 +178,10 @@ pub struct StartListenerStep{
 #[derive(Debug, Clone,PartialEq)]
 pub struct StubResponse{
     status : Expression,
-    body : Expression
+    body : Option<Expression>
 }
 impl StubResponse {
-    pub fn from(status:Option<Expression>,body:Expression)->Self{
+    pub fn from(status:Option<Expression>,body:Option<Expression>)->Self{
         Self {
             status:status.map(|s|s ).unwrap_or(Expression::Constant(Value::PositiveInteger(200))),
             body
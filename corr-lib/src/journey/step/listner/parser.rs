@@ -1,6 +1,7 @@
 use crate::parser::{Parsable, ParseResult, ws};
 use nom::combinator::{map, opt};
-use nom::sequence::{tuple};
+use nom::sequence::{preceded, tuple};
+use nom::branch::alt;
 use crate::template::rest::{RestVerb};
 use nom::bytes::complete::tag;
 
@@ -24,16 +25,16 @@ impl Parsable for Stub {
                 opt(tuple((ws(tag("matching")), ws(tag("request")),ws(ExtractableRestData::parser)))),
                 ws(tag("{")),
                 many0(ws(Step::parser)),
-                tuple((
-                    ws(tag("respond")),
-                    ws(tag("with")),
-                    opt(tuple((
-                        ws(tag("status")),
-                        ws(Expression::parser),
-                        ws(tag("and"))))),
-                    ws(tag("body")),
-                    ws(Expression::parser),
-                )),
+                preceded(
+                    tuple((ws(tag("respond")), ws(tag("with")))),
+                    alt((
+                        map(tuple((
+                            preceded(ws(tag("status")), ws(Expression::parser)),
+                            opt(preceded(tuple((ws(tag("and")), ws(tag("body")))), ws(Expression::parser))))),
+                            |(status, body)| (Option::Some(status), body)),
+                        map(preceded(ws(tag("body")), ws(Expression::parser)), |body| (Option::None, Option::Some(body))),
+                    ))
+                ),
                 ws(tag("}")),
             )),
             |(_,
@@ -42,13 +43,13 @@ impl Parsable for Stub {
                  rd,
                  _,
                  steps,
-                 (_,_,status,_,body),_)| {
+                 (status,body),_)| {
                 Stub{
                     rest_data:rd.map(|(_,_,r)|r).unwrap_or(ExtractableRestData{headers:Option::None,body:Option::None}),
                     method,
                     url,
                     steps,
-                    response: StubResponse::from(status.map(|(_,s,_)|s),body)
+                    response: StubResponse::from(status,body)
                 }
             }
         )(input)
@@ -106,7 +107,7 @@ mod tests{
                     ,Option::Some(Variable::new("id"))),
                 response:StubResponse{
                     status:Expression::Constant(Value::PositiveInteger(200)),
-                    body:Expression::Variable(format!("name"),Option::None)
+                    body:Option::Some(Expression::Variable(format!("name"),Option::None))
                 }
             }]
         })
@@ -130,4 +131,38 @@ mod tests{
         assert_no_error(j,StartListenerStep::parser(j))
 
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn should_parse_stubs_without_response_body(){
+        let j= r#"listen on p1 with {
+            on head with url text `/health` {
+                respond with status 200
+            }
+            on method "PROPFIND" with url text `/files` {
+                respond with status 207 and body files
+            }
+        }"#;
+        assert_if(j,StartListenerStep::parser(j),StartListenerStep{
+            port:Expression::Variable(format!("p1"),Option::None),
+            stubs:vec![Stub{
+                rest_data:ExtractableRestData{ headers:Option::None, body:Option::None },
+                method:RestVerb::HEAD,
+                steps:vec![],
+                url:ExtractableText::Multi(Option::Some("/health".to_string()),vec![],Option::None),
+                response:StubResponse{
+                    status:Expression::Constant(Value::PositiveInteger(200)),
+                    body:Option::None
+                }
+            },Stub{
+                rest_data:ExtractableRestData{ headers:Option::None, body:Option::None },
+                method:RestVerb::Custom(format!("PROPFIND")),
+                steps:vec![],
+                url:ExtractableText::Multi(Option::Some("/files".to_string()),vec![],Option::None),
+                response:StubResponse{
+                    status:Expression::Constant(Value::PositiveInteger(207)),
+                    body:Option::Some(Expression::Variable(format!("files"),Option::None))
+                }
+            }]
+        })
+    }
+}
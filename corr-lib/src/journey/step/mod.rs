@@ -12,6 +12,7 @@ use crate::journey::step::system::SystemStep;
 use async_trait::async_trait;
 use crate::core::runtime::Context;
 use crate::journey::step::rest::RestSetp;
+use crate::journey::step::rest::cookie::CookieStep;
 use crate::journey::step::listner::StartListenerStep;
 use tokio::task::JoinHandle;
 use crate::journey::step::db::{DefineConnectionStep, ExecuteStep};
@@ -19,6 +20,7 @@ use crate::journey::step::websocket::client::{WebSocketClientConnectStep, WebSoc
 use crate::journey::step::websocket::server::{WebSocketServerSendToClient, WebSocketServerStep};
 
 #[derive(Debug, Clone,PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum Step{
     System(SystemStep),
     Rest(RestSetp),
@@ -30,6 +32,7 @@ pub enum Step{
     WebSocketClientSendMessage(WebSocketSendStep),
     WebSocketServerSendToClient(WebSocketServerSendToClient),
     WebSocketClientClose(WebSocketCloseStep),
+    Cookie(CookieStep),
     // Rest(RestStep)
 }
 
@@ -67,6 +70,9 @@ impl Executable for Step{
             },
             Step::WebSocketClientClose(ws)=>{
                 ws.execute(context).await
+            },
+            Step::Cookie(cs)=>{
+                cs.execute(context).await
             }
             // Step::Rest(rest_step)=>{
             //     rest_step.execute(context).await
@@ -105,6 +111,9 @@ impl Executable for Step{
             },
             Step::WebSocketClientClose(cc)=>{
                 cc.get_deps()
+            },
+            Step::Cookie(cs)=>{
+                cs.get_deps()
             }
             // Step::Rest(rest_step)=>{
             //     rest_step.execute(context).await
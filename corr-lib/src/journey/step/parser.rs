@@ -5,6 +5,7 @@ use crate::journey::step::system::SystemStep;
 use crate::parser::Parsable;
 use nom::branch::alt;
 use crate::journey::step::rest::RestSetp;
+use crate::journey::step::rest::cookie::CookieStep;
 use crate::journey::step::listner::StartListenerStep;
 use crate::journey::step::db::{DefineConnectionStep, ExecuteStep};
 use crate::journey::step::websocket::client::{WebSocketClientConnectStep, WebSocketCloseStep, WebSocketSendStep};
@@ -19,6 +20,7 @@ impl Parsable for Step{
             map(ws(WebSocketCloseStep::parser),Step::WebSocketClientClose),
             map(ws(WebSocketServerStep::parser),Step::WebSocketServer),
             map(ws(StartListenerStep::parser),Step::Listner),
+            map(ws(CookieStep::parser),Step::Cookie),
             map(ws(SystemStep::parser),Step::System),
             map(ws(DefineConnectionStep::parser),Step::DefineConnection),
             map(ws(ExecuteStep::parser), Step::InsertStep),
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use hyper::{Body, Client, Request};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use anyhow::{anyhow, bail, Result};
+use crate::journey::step::rest::tls::HttpsConnector;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenScope{
+    //Every VU fetches its own token, like separate users would
+    Vu,
+    Global,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct OAuth2Request{
+    pub url:String,
+    pub client_id:String,
+    pub client_secret:Option<String>,
+    pub scope:Option<String>,
+    pub username:Option<String>,
+    pub password:Option<String>,
+    pub cache:TokenScope,
+}
+impl OAuth2Request{
+    fn key(&self)->String{
+        format!("{}|{}|{}|{}",self.url,self.client_id,self.username.clone().unwrap_or_default(),self.scope.clone().unwrap_or_default())
+    }
+    fn grant(&self)->Vec<(&'static str,String)>{
+        let mut form = if let (Some(username),Some(password)) = (&self.username,&self.password) {
+            vec![("grant_type","password".to_string()),("username",username.clone()),("password",password.clone())]
+        } else {
+            vec![("grant_type","client_credentials".to_string())]
+        };
+        if let Some(scope) = &self.scope {
+            form.push(("scope",scope.clone()));
+        }
+        form
+    }
+}
+#[derive(Deserialize)]
+struct TokenResponse{
+    access_token:String,
+    expires_in:Option<u64>,
+    refresh_token:Option<String>,
+}
+struct Token{
+    access:String,
+    refresh:Option<String>,
+    fetched:SystemTime,
+    expires:Option<SystemTime>,
+}
+impl Token{
+    //Refreshed a little before expiry so requests in flight don't carry an expired token
+    fn is_fresh(&self,now:SystemTime)->bool{
+        match self.expires {
+            Some(expires)=>{
+                let lifetime = expires.duration_since(self.fetched).unwrap_or_default();
+                now + lifetime.min(Duration::from_secs(60)) / 2 < expires
+            }
+            None=>true,
+        }
+    }
+}
+//Every token has its own slot so fetching one doesn't hold up the others
+type Slots = Arc<Mutex<HashMap<String,Arc<Mutex<Option<Token>>>>>>;
+#[derive(Clone, Default)]
+pub struct TokenCache{
+    global:Slots,
+    vu:Slots,
+}
+impl TokenCache{
+    pub fn for_vu(&self)->Self{
+        TokenCache{
+            global:self.global.clone(),
+            vu:Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    pub async fn token(&self,request:&OAuth2Request,client:&Client<HttpsConnector>,now:SystemTime)->Result<String>{
+        let cache = match request.cache {
+            TokenScope::Vu=>&self.vu,
+            TokenScope::Global=>&self.global,
+        };
+        let slot = cache.lock().await.entry(request.key()).or_default().clone();
+        //Held while fetching so VUs sharing a token wait for one fetch
+        let mut held = slot.lock().await;
+        let token = match held.take() {
+            Some(token) if token.is_fresh(now)=>token,
+            Some(Token{refresh:Some(refresh),..})=>{
+                match fetch(client,request,vec![("grant_type","refresh_token".to_string()),("refresh_token",refresh)],now).await {
+                    Ok(token)=>token,
+                    Err(_)=>fetch(client,request,request.grant(),now).await?,
+                }
+            }
+            _=>fetch(client,request,request.grant(),now).await?,
+        };
+        let access = token.access.clone();
+        *held = Option::Some(token);
+        Ok(access)
+    }
+}
+async fn fetch(client:&Client<HttpsConnector>,request:&OAuth2Request,mut form:Vec<(&'static str,String)>,now:SystemTime)->Result<Token>{
+    form.push(("client_id",request.client_id.clone()));
+    if let Some(secret) = &request.client_secret {
+        form.push(("client_secret",secret.clone()));
+    }
+    let token_request = Request::post(request.url.as_str())
+        .header("Content-Type","application/x-www-form-urlencoded")
+        .header("Accept","application/json")
+        .body(Body::from(serde_urlencoded::to_string(&form)?))?;
+    let response = client.request(token_request).await?;
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    if !status.is_success() {
+        bail!("Token endpoint {} responded with {}: {}",request.url,status,String::from_utf8_lossy(&body));
+    }
+    let token:TokenResponse = serde_json::from_slice(&body)
+        .map_err(|e|anyhow!("Invalid token response from {}: {}",request.url,e))?;
+    Ok(Token{
+        access:token.access_token,
+        refresh:token.refresh_token,
+        fetched:now,
+        expires:token.expires_in.map(|secs|now + Duration::from_secs(secs)),
+    })
+}
+#[cfg(test)]
+mod tests{
+    use std::time::{Duration, SystemTime};
+    use hyper::{Body, Response};
+    use crate::journey::step::rest::auth::{OAuth2Request, Token, TokenCache, TokenScope};
+    use crate::journey::step::rest::client::{HttpClients, HttpConfig};
+    use crate::journey::step::rest::testing::{can_bind_local_socket, serve};
+
+    fn oauth2(url:String)->OAuth2Request{
+        OAuth2Request{url,client_id:"corr".to_string(),client_secret:Option::None,scope:Option::None,username:Option::None,password:Option::None,cache:TokenScope::Global}
+    }
+
+    #[test]
+    fn should_refresh_token_before_expiry(){
+        let fetched = SystemTime::now();
+        let token = Token{access:"a".to_string(),refresh:Option::None,fetched,expires:Option::Some(fetched + Duration::from_secs(3600))};
+        assert!(token.is_fresh(fetched + Duration::from_secs(3560)));
+        assert!(!token.is_fresh(fetched + Duration::from_secs(3575)));
+        let short = Token{access:"a".to_string(),refresh:Option::None,fetched,expires:Option::Some(fetched + Duration::from_secs(10))};
+        assert!(short.is_fresh(fetched + Duration::from_secs(4)));
+        assert!(!short.is_fresh(fetched + Duration::from_secs(6)));
+    }
+    #[tokio::test]
+    async fn should_not_hold_other_tokens_while_fetching_one(){
+        if !can_bind_local_socket() {
+            return;
+        }
+        //Accepts connections but never answers
+        let hung = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hung_url = format!("http://{}/oauth/token",hung.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = vec![];
+            while let Ok((stream,_)) = hung.accept().await {
+                held.push(stream);
+            }
+        });
+        let url = format!("http://{}/oauth/token",serve(|_req|Response::new(Body::from(r#"{"access_token": "t1"}"#))));
+        let clients = HttpClients::new(HttpConfig::default()).unwrap();
+        let cache = TokenCache::default();
+        let (hung_clients,hung_cache) = (clients.clone(),cache.clone());
+        tokio::spawn(async move { hung_cache.token(&oauth2(hung_url),hung_clients.client(),SystemTime::now()).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let token = tokio::time::timeout(Duration::from_secs(1),cache.token(&oauth2(url),clients.client(),SystemTime::now())).await;
+        assert_eq!(token.unwrap().unwrap(),"t1".to_string());
+    }
+}
@@ -0,0 +1,266 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use hyper::{Body, Client, Uri};
+use hyper::service::Service;
+use serde::{Deserialize, Serialize};
+use tokio::net::{lookup_host, TcpStream};
+use anyhow::{anyhow, bail, Result};
+use crate::journey::step::rest::auth::TokenCache;
+use crate::journey::step::rest::cookie::CookieJar;
+use crate::journey::step::rest::proxy::{self, ProxiedStream, ProxyConfig, ResolveOverride};
+use crate::journey::step::rest::redirect::RedirectPolicy;
+use crate::journey::step::rest::timing::ConnectTiming;
+use crate::journey::step::rest::tls::{https_connector, native_connector, HttpsConnector, TlsConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConnectionModel{
+    //One pool for every VU
+    Shared,
+    //Every VU keeps its own pool, like a browser or app instance would
+    PerVu,
+    //No connection is reused
+    PerRequest,
+}
+impl ConnectionModel{
+    pub fn as_str(&self)->&'static str{
+        match self {
+            ConnectionModel::Shared=>"shared",
+            ConnectionModel::PerVu=>"per-vu",
+            ConnectionModel::PerRequest=>"per-request",
+        }
+    }
+}
+impl FromStr for ConnectionModel{
+    type Err = anyhow::Error;
+
+    fn from_str(s:&str)->Result<Self>{
+        match s {
+            "shared"=>Ok(ConnectionModel::Shared),
+            "per-vu"=>Ok(ConnectionModel::PerVu),
+            "per-request"=>Ok(ConnectionModel::PerRequest),
+            _=>bail!("Expected connection model to be one of shared, per-vu or per-request"),
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpConfig{
+    pub model:ConnectionModel,
+    pub pool_size:Option<usize>,
+    pub idle_timeout:Option<Duration>,
+    //Used by requests that don't set their own timeout
+    pub timeout:Option<Duration>,
+    pub tls:TlsConfig,
+    //Used by requests that don't set their own redirect policy
+    pub redirects:RedirectPolicy,
+    pub proxy:ProxyConfig,
+    pub resolve:Vec<ResolveOverride>,
+}
+impl Default for HttpConfig{
+    fn default()->Self{
+        HttpConfig{
+            model:ConnectionModel::Shared,
+            pool_size:Option::None,
+            idle_timeout:Option::None,
+            timeout:Option::None,
+            tls:TlsConfig::default(),
+            redirects:RedirectPolicy::default(),
+            proxy:ProxyConfig::default(),
+            resolve:vec![],
+        }
+    }
+}
+//Connects through proxies and resolve overrides, timing the lookup and connect so the response that opened the connection can report it
+#[derive(Clone)]
+pub struct TcpConnector{
+    proxy:Arc<ProxyConfig>,
+    resolve:Arc<Vec<ResolveOverride>>,
+}
+impl TcpConnector{
+    fn new(config:&HttpConfig)->Self{
+        TcpConnector{proxy:Arc::new(config.proxy.clone()),resolve:Arc::new(config.resolve.clone())}
+    }
+    fn open(&self,uri:Uri)-><Self as Service<Uri>>::Future{
+        let host = uri.host().unwrap_or("").to_string();
+        let port = proxy::default_port(&uri);
+        let via = self.proxy.proxy_for(&uri);
+        let address = match &via {
+            Some(proxy)=>proxy::address(&self.resolve,proxy.host_str().unwrap_or(""),proxy.port_or_known_default().unwrap_or(80)),
+            None=>proxy::address(&self.resolve,host.as_str(),port),
+        };
+        //Only plain http is forwarded by the proxy, everything else goes through a tunnel
+        let forwarded = uri.scheme_str() == Option::Some("http");
+        Box::pin(async move {
+            //Resolved here rather than by the tcp connect so lookups are timed on their own
+            let started = Instant::now();
+            let addresses:Vec<SocketAddr> = lookup_host(address.as_str()).await?.collect();
+            let dns = started.elapsed();
+            let mut stream = TcpStream::connect(addresses.as_slice()).await?;
+            let proxied = match via {
+                Some(_) if forwarded=>true,
+                Some(proxy)=>{
+                    proxy::tunnel(&mut stream,host.trim_matches(|c|c == '[' || c == ']'),port,&proxy).await?;
+                    false
+                }
+                None=>false,
+            };
+            Ok(ProxiedStream::new(stream,proxied,ConnectTiming::new(dns,started.elapsed() - dns)))
+        })
+    }
+}
+impl Service<Uri> for TcpConnector{
+    type Response = ProxiedStream;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<Self::Response,Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self,_cx:&mut TaskContext<'_>)->Poll<std::result::Result<(),Self::Error>>{
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self,uri:Uri)->Self::Future{
+        self.open(uri)
+    }
+}
+//Clients for plain and tls traffic, the tls setup is built once per configuration and reused by every pool
+#[derive(Clone)]
+pub struct HttpClients{
+    config:HttpConfig,
+    tcp:TcpConnector,
+    connector:HttpsConnector,
+    websocket_tls:native_tls::TlsConnector,
+    client:Client<HttpsConnector>,
+    //Scenario of the VU using the clients, connections it opens are reported against it
+    scenario:Option<String>,
+    tokens:TokenCache,
+    cookies:CookieJar,
+}
+impl HttpClients{
+    pub fn new(config:HttpConfig)->Result<Self>{
+        config.proxy.validate()?;
+        let tcp = TcpConnector::new(&config);
+        let connector = https_connector(&config.tls,tcp.clone())?;
+        let websocket_tls = native_connector(&config.tls)?;
+        Ok(HttpClients{
+            client:Self::build(&config,connector.clone()),
+            config,
+            tcp,
+            connector,
+            websocket_tls,
+            scenario:Option::None,
+            tokens:TokenCache::default(),
+            cookies:CookieJar::default(),
+        })
+    }
+    fn build(config:&HttpConfig,connector:HttpsConnector)->Client<HttpsConnector>{
+        let mut builder = Client::builder();
+        match config.model {
+            ConnectionModel::PerRequest=>{
+                builder.pool_max_idle_per_host(0);
+            }
+            _=>{
+                if let Some(size) = config.pool_size {
+                    builder.pool_max_idle_per_host(size);
+                }
+            }
+        }
+        if let Some(timeout) = config.idle_timeout {
+            builder.pool_idle_timeout(timeout);
+        }
+        builder.build::<_,Body>(connector)
+    }
+    pub fn config(&self)->&HttpConfig{
+        &self.config
+    }
+    pub fn client(&self)->&Client<HttpsConnector>{
+        &self.client
+    }
+    pub fn tokens(&self)->&TokenCache{
+        &self.tokens
+    }
+    pub fn cookies(&self)->&CookieJar{
+        &self.cookies
+    }
+    //Plain connection for uri made like http ones are, for protocols that do their own tls like websockets
+    pub async fn connect(&self,uri:Uri)->Result<TcpStream>{
+        let stream = self.tcp.open(uri).await.map_err(|e|anyhow!("{}",e))?;
+        Ok(stream.into_inner())
+    }
+    //Websockets only do native tls, set up from the same configuration as http
+    pub fn websocket_tls(&self)->&native_tls::TlsConnector{
+        &self.websocket_tls
+    }
+    //Clients a new VU of scenario should use, with its own cookies, only per VU connections get a pool of their own
+    pub fn for_vu(&self,scenario:&str)->Self{
+        let client = if self.config.model == ConnectionModel::PerVu {
+            Self::build(&self.config,self.connector.clone())
+        } else {
+            self.client.clone()
+        };
+        HttpClients{
+            client,
+            scenario:Option::Some(scenario.to_string()),
+            tokens:self.tokens.for_vu(),
+            cookies:CookieJar::default(),
+            ..self.clone()
+        }
+    }
+    //Tags connections opened by these clients are reported with
+    pub fn connection_tags(&self)->Vec<(String,String)>{
+        let mut tags = vec![("model".to_string(),self.config.model.as_str().to_string())];
+        if let Some(scenario) = &self.scenario {
+            tags.push(("scenario".to_string(),scenario.clone()));
+        }
+        tags
+    }
+}
+#[cfg(test)]
+mod tests{
+    use std::time::Duration;
+    use hyper::{Body, Response};
+    use crate::journey::step::rest::client::{ConnectionModel, HttpClients, HttpConfig};
+    use crate::journey::step::rest::testing::{can_bind_local_socket, serve};
+    use crate::journey::step::rest::timing::Timings;
+
+    //Connections each VU opened, as reported by the responses that opened them
+    async fn opened_for(model:ConnectionModel,vus:usize,requests:usize)->Vec<u64>{
+        let url = format!("http://{}/",serve(|_req|Response::new(Body::from("ok"))));
+        let clients = HttpClients::new(HttpConfig{model,..HttpConfig::default()}).unwrap();
+        let mut opened = vec![];
+        for _ in 0..vus {
+            let vu = clients.for_vu("Browse");
+            let mut count = 0;
+            for _ in 0..requests {
+                let response = vu.client().get(url.parse().unwrap()).await.unwrap();
+                count += Timings::of(&response,Duration::from_millis(0),false).opened;
+                hyper::body::to_bytes(response.into_body()).await.unwrap();
+            }
+            opened.push(count);
+        }
+        opened
+    }
+
+    #[tokio::test]
+    async fn should_open_connections_as_per_connection_model(){
+        if !can_bind_local_socket() {
+            return;
+        }
+        assert_eq!(opened_for(ConnectionModel::Shared,2,3).await,vec![1,0]);
+        assert_eq!(opened_for(ConnectionModel::PerVu,2,3).await,vec![1,1]);
+        assert_eq!(opened_for(ConnectionModel::PerRequest,2,3).await,vec![3,3]);
+    }
+    #[test]
+    fn should_tag_connections_with_scenario_and_model(){
+        let clients = HttpClients::new(HttpConfig{model:ConnectionModel::PerVu,..HttpConfig::default()}).unwrap();
+        assert_eq!(clients.connection_tags(),vec![("model".to_string(),"per-vu".to_string())]);
+        assert_eq!(clients.for_vu("Browse").connection_tags(),vec![("model".to_string(),"per-vu".to_string()),("scenario".to_string(),"Browse".to_string())]);
+    }
+    #[test]
+    fn should_parse_connection_model(){
+        assert_eq!("per-vu".parse::<ConnectionModel>().unwrap(),ConnectionModel::PerVu);
+        assert!("pooled".parse::<ConnectionModel>().is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use async_trait::async_trait;
+use tokio::task::JoinHandle;
+use url::Url;
+use anyhow::{anyhow, Result};
+use crate::core::runtime::Context;
+use crate::journey::Executable;
+use crate::template::{Expression, Fillable};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cookie{
+    pub name:String,
+    pub value:String,
+    domain:String,
+    //Without a Domain attribute a cookie goes back only to the host that set it
+    host_only:bool,
+    path:String,
+    expires:Option<SystemTime>,
+    secure:bool,
+}
+impl Cookie{
+    //Parses a Set-Cookie header received from url, None when it should be ignored
+    pub fn parse(set_cookie:&str,url:&Url,now:SystemTime)->Option<Cookie>{
+        let host = url.host_str()?.to_lowercase();
+        let mut parts = set_cookie.split(';');
+        let (name,value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Option::None;
+        }
+        let mut cookie = Cookie{
+            name:name.to_string(),
+            value:value.trim().to_string(),
+            domain:host.clone(),
+            host_only:true,
+            path:default_path(url),
+            expires:Option::None,
+            secure:false,
+        };
+        let mut max_age = Option::None;
+        for attribute in parts {
+            let (key,val) = attribute.split_once('=').unwrap_or((attribute,""));
+            let val = val.trim();
+            match key.trim().to_lowercase().as_str() {
+                "expires"=>{
+                    if let Some(expires) = parse_date(val) {
+                        cookie.expires = Option::Some(expires);
+                    }
+                }
+                "max-age"=>max_age = val.parse::<i64>().ok(),
+                "domain"=>{
+                    let domain = val.trim_start_matches('.').to_lowercase();
+                    if !domain.is_empty() {
+                        if !domain_matches(&host,&domain) {
+                            return Option::None;
+                        }
+                        cookie.domain = domain;
+                        cookie.host_only = false;
+                    }
+                }
+                "path" if val.starts_with('/')=>cookie.path = val.to_string(),
+                "secure"=>cookie.secure = true,
+                _=>{}
+            }
+        }
+        //Max-Age wins over Expires
+        if let Some(secs) = max_age {
+            cookie.expires = Option::Some(if secs > 0 { now + Duration::from_secs(secs as u64) } else { SystemTime::UNIX_EPOCH });
+        }
+        Option::Some(cookie)
+    }
+    fn is_expired(&self,now:SystemTime)->bool{
+        self.expires.map(|expires|expires <= now).unwrap_or(false)
+    }
+    fn matches(&self,url:&Url,now:SystemTime)->bool{
+        let host = url.host_str().unwrap_or("").to_lowercase();
+        let domain = if self.host_only { host == self.domain } else { domain_matches(&host,&self.domain) };
+        let secure = !self.secure || url.scheme() == "https" || url.scheme() == "wss";
+        domain && secure && path_matches(url.path(),&self.path) && !self.is_expired(now)
+    }
+    fn same_as(&self,other:&Cookie)->bool{
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+}
+//Cookie date as per RFC 6265 section 5.1.1, takes `Wed, 21 Oct 2015 07:28:00 GMT`,
+//`Wed, 21-Oct-2015 07:28:00 GMT`, `Wednesday, 21-Oct-15 07:28:00 GMT` and asctime alike
+fn parse_date(value:&str)->Option<SystemTime>{
+    const MONTHS:[&str; 12] = ["jan","feb","mar","apr","may","jun","jul","aug","sep","oct","nov","dec"];
+    let (mut time,mut day,mut month,mut year) = (Option::None,Option::None,Option::None,Option::None);
+    let digits = |token:&str,min:usize,max:usize|->Option<u32>{
+        let count = token.chars().take_while(|c|c.is_ascii_digit()).count();
+        if count < min || count > max { Option::None } else { token[..count].parse().ok() }
+    };
+    for token in value.split(|c:char|!(c.is_ascii_alphanumeric() || c == ':')).filter(|token|!token.is_empty()) {
+        if time.is_none() {
+            let fields:Vec<Option<u32>> = token.split(':').map(|field|digits(field,1,2)).collect();
+            if let [Some(hour),Some(minute),Some(second)] = fields[..] {
+                time = Option::Some((hour,minute,second));
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some(found) = digits(token,1,2) {
+                day = Option::Some(found);
+                continue;
+            }
+        }
+        if month.is_none() && token.len() >= 3 {
+            if let Some(index) = MONTHS.iter().position(|name|token[..3].eq_ignore_ascii_case(name)) {
+                month = Option::Some(index as u32 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some(found) = digits(token,2,4) {
+                year = Option::Some(match found {
+                    70..=99=>found + 1900,
+                    0..=69=>found + 2000,
+                    _=>found,
+                });
+            }
+        }
+    }
+    let (hour,minute,second) = time?;
+    let date = chrono::NaiveDate::from_ymd_opt(year? as i32,month?,day?)?.and_hms_opt(hour,minute,second)?;
+    let secs = date.timestamp();
+    Option::Some(if secs > 0 { SystemTime::UNIX_EPOCH + Duration::from_secs(secs as u64) } else { SystemTime::UNIX_EPOCH })
+}
+fn domain_matches(host:&str,domain:&str)->bool{
+    host == domain || host.ends_with(&format!(".{}",domain))
+}
+fn path_matches(request_path:&str,path:&str)->bool{
+    request_path == path
+        || (request_path.starts_with(path) && (path.ends_with('/') || request_path[path.len()..].starts_with('/')))
+}
+fn default_path(url:&Url)->String{
+    match url.path().rfind('/') {
+        Some(0) | None=>"/".to_string(),
+        Some(index)=>url.path()[..index].to_string(),
+    }
+}
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar{
+    cookies:Arc<Mutex<Vec<Cookie>>>,
+}
+impl CookieJar{
+    pub fn store<'a>(&self,url:&str,set_cookies:impl Iterator<Item = &'a str>,now:SystemTime){
+        if let Ok(url) = Url::parse(url) {
+            let mut cookies = self.cookies.lock().unwrap();
+            for set_cookie in set_cookies {
+                if let Some(cookie) = Cookie::parse(set_cookie,&url,now) {
+                    cookies.retain(|existing|!existing.same_as(&cookie));
+                    if !cookie.is_expired(now) {
+                        cookies.push(cookie);
+                    }
+                }
+            }
+        }
+    }
+    //Value of the Cookie header for a request to url, longer paths first
+    pub fn header_for(&self,url:&str,now:SystemTime)->Option<String>{
+        let url = Url::parse(url).ok()?;
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie|!cookie.is_expired(now));
+        let mut matching:Vec<&Cookie> = cookies.iter().filter(|cookie|cookie.matches(&url,now)).collect();
+        if matching.is_empty() {
+            return Option::None;
+        }
+        matching.sort_by_key(|cookie|std::cmp::Reverse(cookie.path.len()));
+        Option::Some(matching.iter().map(|cookie|format!("{}={}",cookie.name,cookie.value)).collect::<Vec<String>>().join("; "))
+    }
+    pub fn get(&self,name:&str,url:Option<&str>,now:SystemTime)->Option<String>{
+        let url = url.and_then(|url|Url::parse(url).ok());
+        self.cookies.lock().unwrap().iter()
+            .filter(|cookie|cookie.name == name && !cookie.is_expired(now))
+            .find(|cookie|url.as_ref().map(|url|cookie.matches(url,now)).unwrap_or(true))
+            .map(|cookie|cookie.value.clone())
+    }
+    pub fn clear(&self){
+        self.cookies.lock().unwrap().clear();
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum CookieStep{
+    Set(Expression,Expression,Expression),
+    Clear,
+}
+#[async_trait]
+impl Executable for CookieStep{
+    async fn execute(&self,context:&Context)->Result<Vec<JoinHandle<Result<bool>>>>{
+        match self {
+            CookieStep::Set(name,value,url)=>{
+                let name:String = name.fill(context).await?;
+                let value:String = value.fill(context).await?;
+                let url:String = url.fill(context).await?;
+                Url::parse(url.as_str()).map_err(|e|anyhow!("Invalid cookie url {}: {}",url,e))?;
+                context.http.cookies().store(url.as_str(),std::iter::once(format!("{}={}",name,value).as_str()),context.clock.now());
+            }
+            CookieStep::Clear=>context.http.cookies().clear(),
+        }
+        Ok(vec![])
+    }
+
+    fn get_deps(&self)->Vec<String>{
+        vec![]
+    }
+}
+#[cfg(test)]
+mod tests{
+    use std::time::{Duration, SystemTime};
+    use crate::journey::step::rest::cookie::{parse_date, CookieJar};
+
+    #[test]
+    fn should_send_cookies_back_as_per_domain_and_path(){
+        let jar = CookieJar::default();
+        let now = SystemTime::now();
+        jar.store("http://app.example.com/account/login",vec![
+            "session=abc; Path=/; HttpOnly",
+            "pref=dark; Domain=.example.com; Path=/account",
+            "local=1",
+            "evil=1; Domain=other.com",
+        ].into_iter(),now);
+        assert_eq!(jar.header_for("http://app.example.com/account/profile",now),Option::Some("pref=dark; local=1; session=abc".to_string()));
+        assert_eq!(jar.header_for("http://api.example.com/account",now),Option::Some("pref=dark".to_string()));
+        assert_eq!(jar.header_for("http://app.example.com/accounts",now),Option::Some("session=abc".to_string()));
+        assert_eq!(jar.header_for("http://other.com/",now),Option::None);
+    }
+    #[test]
+    fn should_expire_replace_and_keep_secure_cookies_to_https(){
+        let jar = CookieJar::default();
+        let now = SystemTime::now();
+        jar.store("https://example.com/",vec!["token=1; Secure","short=1; Max-Age=10","old=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT"].into_iter(),now);
+        assert_eq!(jar.header_for("http://example.com/",now),Option::Some("short=1".to_string()));
+        assert_eq!(jar.header_for("https://example.com/",now + Duration::from_secs(11)),Option::Some("token=1".to_string()));
+        jar.store("https://example.com/",vec!["token=2; Secure"].into_iter(),now);
+        assert_eq!(jar.get("token",Option::None,now),Option::Some("2".to_string()));
+        jar.store("https://example.com/",vec!["token=; Max-Age=0"].into_iter(),now);
+        assert_eq!(jar.get("token",Option::None,now),Option::None);
+    }
+    #[test]
+    fn should_parse_cookie_dates_in_every_common_form(){
+        let expected = Option::Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1445412480));
+        for date in ["Wed, 21 Oct 2015 07:28:00 GMT","Wed, 21-Oct-2015 07:28:00 GMT","Wednesday, 21-Oct-15 07:28:00 GMT","Wed Oct 21 07:28:00 2015"] {
+            assert_eq!(parse_date(date),expected,"{}",date);
+        }
+        assert_eq!(parse_date("tomorrow"),Option::None);
+    }
+}
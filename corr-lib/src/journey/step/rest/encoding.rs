@@ -0,0 +1,65 @@
+use std::io::Read;
+use encoding_rs::{Encoding, UTF_8};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use hyper::header::{HeaderMap, CONTENT_ENCODING, CONTENT_TYPE};
+
+#[cfg(feature = "brotli")]
+pub const ACCEPT_ENCODING:&str = "gzip, deflate, br";
+#[cfg(not(feature = "brotli"))]
+pub const ACCEPT_ENCODING:&str = "gzip, deflate";
+
+//Undoes Content-Encoding, bytes are kept as received when they can't be decoded
+pub fn decompress(headers:&HeaderMap,bytes:&[u8])->Vec<u8>{
+    let encoding = headers.get(CONTENT_ENCODING).and_then(|value|value.to_str().ok()).unwrap_or("");
+    let mut decoded = Vec::new();
+    let result = match encoding.trim().to_lowercase().as_str() {
+        "gzip" | "x-gzip"=>GzDecoder::new(bytes).read_to_end(&mut decoded),
+        "deflate"=>ZlibDecoder::new(bytes).read_to_end(&mut decoded),
+        #[cfg(feature = "brotli")]
+        "br"=>brotli_decompressor::Decompressor::new(bytes,4096).read_to_end(&mut decoded),
+        _=>return bytes.to_vec(),
+    };
+    match result {
+        Ok(_)=>decoded,
+        Err(_)=>bytes.to_vec(),
+    }
+}
+//Text of a body as per the charset of its Content-Type, UTF-8 when there is none
+pub fn decode_text(headers:&HeaderMap,bytes:&[u8])->String{
+    let encoding = headers.get(CONTENT_TYPE)
+        .and_then(|value|value.to_str().ok())
+        .and_then(|content_type|content_type.split(';').skip(1)
+            .filter_map(|param|param.split_once('='))
+            .find(|(key,_)| key.trim().eq_ignore_ascii_case("charset"))
+            .map(|(_,charset)| charset.trim().trim_matches('"').to_string()))
+        .and_then(|charset|Encoding::for_label(charset.as_bytes()))
+        .unwrap_or(UTF_8);
+    let (text,_,_) = encoding.decode(bytes);
+    text.to_string()
+}
+#[cfg(test)]
+mod tests{
+    use std::io::Write;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use hyper::header::{HeaderMap, HeaderValue};
+    use crate::journey::step::rest::encoding::{decode_text, decompress};
+
+    #[test]
+    fn should_decompress_gzip_body(){
+        let mut encoder = GzEncoder::new(Vec::new(),Compression::default());
+        encoder.write_all(br#"{"id":1}"#).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Encoding",HeaderValue::from_static("gzip"));
+        assert_eq!(decompress(&headers,&compressed),br#"{"id":1}"#.to_vec());
+        assert_eq!(decompress(&HeaderMap::new(),b"plain"),b"plain".to_vec());
+    }
+    #[test]
+    fn should_decode_text_as_per_charset(){
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type",HeaderValue::from_static("text/plain; charset=\"ISO-8859-1\""));
+        assert_eq!(decode_text(&headers,&[0x63,0x61,0x66,0xE9]),"café");
+        assert_eq!(decode_text(&HeaderMap::new(),"café".as_bytes()),"café");
+    }
+}
@@ -1,52 +1,66 @@
 pub mod parser;
+pub mod auth;
+pub mod client;
+pub mod cookie;
+pub mod encoding;
+pub mod proxy;
+pub mod redirect;
+pub mod timing;
+pub mod tls;
+#[cfg(test)]
+mod testing;
 
 
 
 
 use crate::template::object::extractable::{Extractable};
-use crate::template::rest::{ RequestBody, RequestHeaders, RestVerb, FillableRequest};
+use crate::template::rest::{ RequestBody, RequestHeader, RequestHeaders, RestVerb, FillableRequest, RetryOn, RetryPolicy, StatusPattern};
+use crate::template::VariableReferenceName;
+use crate::core::Value;
+use std::collections::HashMap;
 use crate::template::rest::extractable::{ExtractableRestData, CorrResponse};
+use crate::journey::step::rest::auth::OAuth2Request;
+use crate::journey::step::rest::redirect::RedirectPolicy;
+use crate::journey::step::rest::timing::Timings;
 use crate::journey::{Executable};
 use crate::core::runtime::Context;
 use crate::template::Fillable;
 use async_trait::async_trait;
 use tokio::task::JoinHandle;
-use hyper::{Request,Body};
+use hyper::{Request,Response,Body};
 use hyper::body::Bytes;
-use hyper_tls::HttpsConnector;
-use hyper::Client;
-use hyper::client::HttpConnector;
-use lazy_static::lazy_static;
-use std::time::{Instant};
+use std::time::{Duration, Instant};
+use std::fmt::{Display, Formatter};
 use anyhow::Result;
-lazy_static! {
-    static ref HTTPCLIENT: Client<HttpConnector> = Client::builder().build::<_, hyper::Body>(HttpConnector::new());
-}
 
 
 #[derive(Debug, Clone,PartialEq)]
 pub struct RestSetp{
     is_async:bool,
     request: FillableRequest,
-    response:Option<ExtractableRestData>
+    response:Option<ExtractableRestData>,
+    status:Option<VariableReferenceName>,
+    full_response:Option<VariableReferenceName>,
+    expect:Vec<StatusPattern>
 }
 #[derive(Debug, Clone,PartialEq)]
 pub struct CorrRequest {
     pub method:RestVerb,
     pub url:String,
     pub body:Option<RequestBody>,
-    pub headers:Option<RequestHeaders>
+    pub headers:Option<RequestHeaders>,
+    pub timeout:Option<Duration>,
+    pub retry:Option<RetryPolicy>,
+    pub redirects:Option<RedirectPolicy>,
+    //Token is fetched when sending, within the timeout and retries of the request
+    pub oauth2:Option<OAuth2Request>
 }
 #[async_trait]
 impl Executable for RestSetp{
 
     async fn execute(&self,context: &Context)->Result<Vec<JoinHandle<Result<bool>>>>{
-        let start = Instant::now();
         let req = self.request.fill(context).await?;
-        rest(req.clone(),self.response.clone(),context,self.is_async).await;
-        let duration = start.elapsed();
-        context.scrapper.ingest("response_time",duration.as_millis() as f64,vec![("method".to_string(),req.method.clone().as_str().to_string()),("url".to_string(),req.url.clone())]).await;
-        context.rest_stats_store.push_stat((req.method,req.url,duration.as_millis())).await;
+        rest(req,self.clone(),context).await;
         return Ok(vec![])
     }
 
@@ -54,103 +68,235 @@ impl Executable for RestSetp{
         vec![]
     }
 }
-pub async fn rest(request: CorrRequest, response:Option<ExtractableRestData>, context:&Context, is_async:bool) {
-    let mut builder = match request.method {
-        RestVerb::GET => Request::get(request.url.clone()),
-        RestVerb::POST => Request::post(request.url.clone()),
-        RestVerb::PATCH => Request::patch(request.url.clone()),
-        RestVerb::PUT => Request::put(request.url.clone()),
-        RestVerb::DELETE => Request::delete(request.url.clone())
-    };
+#[derive(Debug)]
+pub enum RestFailure{
+    Timeout(Duration),
+    Error(hyper::Error),
+    Invalid(hyper::http::Error),
+    Token(anyhow::Error)
+}
+impl Display for RestFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RestFailure::Timeout(timeout)=>write!(f,"timed out after {:?}",timeout),
+            RestFailure::Error(e)=>write!(f,"{}",e),
+            RestFailure::Invalid(e)=>write!(f,"invalid request: {}",e),
+            RestFailure::Token(e)=>write!(f,"unable to get token: {}",e)
+        }
+    }
+}
+fn build_request(request:&CorrRequest,context:&Context)->hyper::http::Result<Request<Body>>{
+    let mut builder = Request::builder()
+        .method(request.method.as_method().as_str())
+        .uri(request.url.clone());
+    let (mut has_content_type,mut has_cookie,mut has_accept_encoding) = (false,false,false);
     if let Some(headers) = request.headers.clone() {
         for header in headers.headers {
+            has_content_type = has_content_type || header.key.eq_ignore_ascii_case("content-type");
+            has_cookie = has_cookie || header.key.eq_ignore_ascii_case("cookie");
+            has_accept_encoding = has_accept_encoding || header.key.eq_ignore_ascii_case("accept-encoding");
             builder = builder.header(header.key.as_str(), header.value.as_str())
         }
     }
-    match &request.body {
-        Option::Some(RequestBody::JSON(_)) => {
-            builder = builder.header("Content-Type", "application/json")
-        },
-        _ => {}
+    if let Some(authorization) = request.url.parse().ok().and_then(|uri| context.http.config().proxy.authorization_for(&uri)) {
+        builder = builder.header("Proxy-Authorization", authorization)
+    }
+    if !has_accept_encoding {
+        builder = builder.header("Accept-Encoding", encoding::ACCEPT_ENCODING)
+    }
+    //Cookies set by hand replace the ones in the jar
+    if !has_cookie {
+        if let Some(cookies) = context.http.cookies().header_for(request.url.as_str(),context.clock.now()) {
+            builder = builder.header("Cookie", cookies)
+        }
+    }
+    if let Some(body) = &request.body {
+        if !has_content_type {
+            builder = builder.header("Content-Type", body.content_type())
+        }
+    }
+    builder.body(request.body.as_ref().map(|bd| Body::from(bd.to_bytes())).unwrap_or_else(Body::empty))
+}
+//Sends the request following redirects and reads the final body, the cookies set on every hop go to the jar
+async fn exchange(request:&CorrRequest,context:&Context)->std::result::Result<Response<Bytes>,RestFailure>{
+    let policy = request.redirects.unwrap_or(context.http.config().redirects);
+    let mut current = request.clone();
+    if let Some(oauth2) = &request.oauth2 {
+        let token = context.http.tokens().token(oauth2,context.http.client(),context.clock.now()).await.map_err(RestFailure::Token)?;
+        let mut headers = current.headers.take().map(|h|h.headers).unwrap_or_default();
+        headers.retain(|header|!header.key.eq_ignore_ascii_case("Authorization"));
+        headers.push(RequestHeader{key:"Authorization".to_string(),value:format!("Bearer {}",token)});
+        current.headers = Option::Some(RequestHeaders{headers});
+    }
+    let mut hops = 0;
+    //Every hop is part of the response, so timings add up over all of them
+    let mut timings = Timings::default();
+    loop {
+        let i_req = build_request(&current,context).map_err(RestFailure::Invalid)?;
+        let sent = Instant::now();
+        let rb = context.http.client().request(i_req).await.map_err(RestFailure::Error)?;
+        let mut hop = Timings::of(&rb,sent.elapsed(),current.url.starts_with("https"));
+        let set_cookies = rb.headers().get_all(hyper::header::SET_COOKIE).iter().filter_map(|value|value.to_str().ok());
+        context.http.cookies().store(current.url.as_str(),set_cookies,context.clock.now());
+        match policy.follow(&current,&rb,hops) {
+            Some(next)=>{
+                //Read so the connection can be reused for the next hop
+                let downloading = Instant::now();
+                let _ = hyper::body::to_bytes(rb.into_body()).await;
+                hop.download = downloading.elapsed();
+                timings.add(&hop);
+                current = next;
+                hops += 1;
+            },
+            None=>{
+                let (mut parts,body) = rb.into_parts();
+                let downloading = Instant::now();
+                let bytes = hyper::body::to_bytes(body).await.map_err(RestFailure::Error)?;
+                hop.download = downloading.elapsed();
+                timings.add(&hop);
+                parts.extensions.insert(timings);
+                return Ok(Response::from_parts(parts,bytes))
+            }
+        }
+    }
+}
+async fn send_once(request:&CorrRequest,timeout:Option<Duration>,context:&Context)->std::result::Result<Response<Bytes>,RestFailure>{
+    let sending = exchange(request,context);
+    if let Some(timeout) = timeout {
+        match tokio::time::timeout(timeout,sending).await {
+            Ok(result)=>result,
+            Err(_)=>Err(RestFailure::Timeout(timeout))
+        }
+    } else {
+        sending.await
+    }
+}
+//Sends the request, retrying as per its retry policy
+async fn send(request:&CorrRequest,context:&Context)->std::result::Result<Response<Bytes>,RestFailure>{
+    let timeout = request.timeout.or(context.http.config().timeout);
+    let tags = vec![("method".to_string(),request.method.as_str().to_string()),("api".to_string(),request.url.clone())];
+    let mut attempt = 0;
+    loop {
+        let outcome = send_once(request,timeout,context).await;
+        let retry_on = match &outcome {
+            Ok(rb)=>RetryOn::Status(rb.status().as_u16()),
+            Err(RestFailure::Timeout(_))=>{
+                context.scrapper.ingest("timeouts",1.0,tags.clone()).await;
+                context.rest_stats_store.push_timeout((request.method.clone(),request.url.clone())).await;
+                RetryOn::Timeout
+            },
+            Err(RestFailure::Error(_)) | Err(RestFailure::Token(_))=>RetryOn::Error,
+            Err(RestFailure::Invalid(_))=>return outcome
+        };
+        match &request.retry {
+            Some(policy) if policy.should_retry(attempt,&retry_on)=>{
+                let mut retry_tags = tags.clone();
+                retry_tags.push(("reason".to_string(),match &outcome {
+                    Ok(rb)=>rb.status().as_u16().to_string(),
+                    Err(e)=>e.to_string()
+                }));
+                context.scrapper.ingest("retries",1.0,retry_tags).await;
+                context.rest_stats_store.push_retry((request.method.clone(),request.url.clone())).await;
+                context.clock.sleep(policy.backoff_for(attempt)).await;
+                attempt += 1;
+            },
+            _=>return outcome
+        }
+    }
+}
+//Response as seen by journeys matching the whole response
+fn response_value(response:&CorrResponse,duration:Duration)->Value{
+    let mut headers = HashMap::new();
+    for (key,value) in response.headers.iter() {
+        headers.insert(key.to_string(),Value::String(String::from_utf8_lossy(value.as_bytes()).to_string()));
+    }
+    let body = if response.body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str::<serde_json::Value>(response.body.as_str()).map(Value::from_json_value).unwrap_or(Value::String(response.body.clone()))
     };
-    if let Ok(i_req) = builder.body(Body::from(request.body.clone().map(|bd| { bd.to_string_body() }).unwrap_or("".to_string()))) {
-        let context = context.clone();
-        let step = async move|| {
-            let i_response ={
-                let uri = i_req.uri().to_string();
-                if uri.starts_with("https") {
-                    let https = HttpsConnector::new();
-                    let client = Client::builder().build::<_, hyper::Body>(https);
-                    client.request(i_req).await
-                } else {
-                    // let http = HttpConnector::new();
-                    // let client = Client::builder().build::<_, hyper::Body>(http);
-                    HTTPCLIENT.request(i_req).await
+    let mut value = HashMap::new();
+    value.insert("status".to_string(),Value::PositiveInteger(response.status as u128));
+    value.insert("headers".to_string(),Value::Map(headers));
+    value.insert("body".to_string(),body);
+    value.insert("duration".to_string(),Value::Duration(duration));
+    Value::Map(value)
+}
+async fn record_response_time(request:&CorrRequest,duration:Duration,context:&Context){
+    context.scrapper.ingest("response_time",duration.as_millis() as f64,vec![("method".to_string(),request.method.as_str().to_string()),("url".to_string(),request.url.clone())]).await;
+    context.rest_stats_store.push_stat((request.method.clone(),request.url.clone(),duration.as_millis())).await;
+}
+pub async fn rest(request: CorrRequest, step:RestSetp, context:&Context) {
+    let clock = context.clock.clone();
+    let context = context.clone();
+    let is_async = step.is_async;
+    let run = async move {
+        let start = Instant::now();
+        match send(&request,&context).await {
+            Ok(rb)=>{
+                let (parts,body_bytes) = rb.into_parts();
+                let mut timings = parts.extensions.get::<Timings>().cloned().unwrap_or_default();
+                record_response_time(&request,timings.response_time(),&context).await;
+                let extracting = Instant::now();
+                let response = CorrResponse {
+                    body: encoding::decode_text(&parts.headers,&encoding::decompress(&parts.headers,&body_bytes)),
+                    headers: parts.headers,
+                    status:parts.status.as_u16()
+                };
+                if let Some(var) = &step.status {
+                    context.define(var.to_string(),Value::PositiveInteger(response.status as u128)).await;
                 }
-            };
-
-            if let Some(er) = response {
-                match i_response {
-                    Ok(rb)=>{
-                        if rb.status().as_u16() < 399 {
-                            let (parts,body) = rb.into_parts();
-                            let body_bytes = hyper::body::to_bytes(body).await.unwrap_or(Bytes::from(""));
-
-                            er.extract_from(&context, CorrResponse {
-                                body: String::from_utf8(body_bytes.to_vec()).unwrap_or("".to_string()), //text_async().await.unwrap().to_string(),
-                                headers: parts.headers,
-                                status:parts.status.as_u16()
-                            }).await
-                        } else {
-                            context.scrapper.ingest("errors",1.0,vec![("api".to_string(),request.url.clone()),("message".to_string(),format!("{}",rb.status().as_str()))]).await;
-                            eprintln!("Rest api {} Failed with code {}", request.url, rb.status())
-                        }
-                    },
-                    Err(e)=>{
-                        context.scrapper.ingest("errors",1.0,vec![("api".to_string(),request.url.clone()),("message".to_string(),format!("{}",e.to_string()))]).await;
-                        eprintln!("Error Response for api {} {:?}", request.url,e)
-                    }
+                if let Some(var) = &step.full_response {
+                    context.define(var.to_string(),response_value(&response,start.elapsed())).await;
                 }
-
-            } else {
-                match i_response {
-                    Ok(rb)=>{
-                        if rb.status().as_u16() > 399 {
-                            context.scrapper.ingest("errors",1.0,vec![(format!("status"),format!("{}",rb.status())),(format!("api"),format!("{}",request.url))]).await;
-                            eprintln!("Rest api {} with body {} Failed with code {}", request.url, request.body.map(|b|b.to_string_body()).unwrap_or(format!("")), rb.status())
-                        }
-                    },
-                    Err(e)=>{
-                        context.scrapper.ingest("errors",1.0,vec![(format!("api"),format!("{}",request.url))]).await;
-                        eprintln!("Error Response for api {} {:?}", request.url,e)
-                    }
+                if !StatusPattern::is_expected(&step.expect,response.status) {
+                    context.mark_failed();
+                    context.scrapper.ingest("errors",1.0,vec![("status".to_string(),parts.status.to_string()),("api".to_string(),request.url.clone())]).await;
+                    eprintln!("Rest api {} with body {} Failed with code {}", request.url, request.body.clone().map(|b|b.to_string_body()).unwrap_or(format!("")), parts.status)
+                }
+                if let Some(er) = &step.response {
+                    er.extract_from(&context, response).await
+                }
+                timings.extraction = extracting.elapsed();
+                if timings.opened > 0 {
+                    context.scrapper.ingest("connections_opened",timings.opened as f64,context.http.connection_tags()).await;
                 }
+                let tags = vec![("method".to_string(),request.method.as_str().to_string()),("url".to_string(),request.url.clone())];
+                for (series,duration) in timings.series() {
+                    context.scrapper.ingest(series,duration.as_secs_f64()*1000.0,tags.clone()).await;
+                }
+                context.rest_stats_store.push_timings((request.method.clone(),request.url.clone(),timings)).await;
+            },
+            Err(e)=>{
+                record_response_time(&request,start.elapsed(),&context).await;
+                context.mark_failed();
+                context.scrapper.ingest("errors",1.0,vec![("api".to_string(),request.url.clone()),("message".to_string(),e.to_string())]).await;
+                eprintln!("Error Response for api {} {:?}", request.url,e)
             }
-        };
-        if is_async {
-            tokio::spawn(step());
-        } else {
-            step().await;
         }
-
+    };
+    if is_async {
+        //Keeps virtual time from moving under a call nobody waits for
+        clock.work().spawn(run);
+    } else {
+        run.await;
     }
-
 }
 #[cfg(test)]
 mod tests {
     use crate::core::proto::{Input};
     use std::sync::{Arc, Mutex};
-    use std::net::TcpListener;
     use crate::journey::{Executable};
     use crate::core::runtime::{Context};
     use crate::parser::Parsable;
     use crate::journey::step::rest::RestSetp;
+    use crate::journey::step::rest::testing::can_bind_local_socket;
+    use crate::journey::step::rest::cookie::CookieStep;
+    use crate::journey::step::listner::StartListenerStep;
+    use crate::template::Expression;
     use crate::core::{DataType, Value};
     use mockito::mock;
-
-    fn can_bind_local_socket() -> bool {
-        TcpListener::bind("127.0.0.1:0").is_ok()
-    }
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     #[tokio::test]
     async fn should_execute_get_rest_step() {
@@ -316,4 +462,386 @@ mod tests {
         assert_eq!(context.get_var_from_store(format!("id")).await, Option::Some(Value::PositiveInteger(1)));
         assert_eq!(context.get_var_from_store(format!("a")).await, Option::Some(Value::String("Hello".to_string())))
     }
+
+    #[tokio::test]
+    async fn should_retry_rest_step_on_listed_status() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let mock = mock("GET", "/flaky")
+            .with_status(503)
+            .expect(3)
+            .create();
+
+        let text = r#"get request {
+            url: text `<%base_url%>/flaky`,
+            retry: { times: 2, backoff: 100ms, on: [502, 503] }
+        }"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), mockito::server_url(), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        step.execute(&context).await.unwrap();
+        mock.assert();
+        assert_eq!(context.rest_stats_store.get_retries().await.len(), 2);
+        assert!(context.has_failed());
+    }
+
+    #[tokio::test]
+    async fn should_time_out_and_retry_rest_step() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let text = r#"get request {
+            url: base_url,
+            timeout: 100ms,
+            retry: { times: 1, on: ["timeout"] }
+        }"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), format!("http://{}/hang", listener.local_addr().unwrap()), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        step.execute(&context).await.unwrap();
+        assert_eq!(context.rest_stats_store.get_timeouts().await.len(), 2);
+        assert_eq!(context.rest_stats_store.get_retries().await.len(), 1);
+        assert!(context.has_failed());
+    }
+
+    #[tokio::test]
+    async fn should_time_out_on_stalled_body() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhalf").await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        });
+        let text = r#"get request {
+            url: base_url,
+            timeout: 200ms
+        }"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), format!("http://{}/stall", address), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        step.execute(&context).await.unwrap();
+        assert_eq!(context.rest_stats_store.get_timeouts().await.len(), 1);
+        assert!(context.has_failed());
+    }
+
+    #[tokio::test]
+    async fn should_execute_head_and_custom_method_rest_steps() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let head = mock("HEAD", "/health")
+            .with_status(200)
+            .with_header("X-Version", "1.2")
+            .create();
+        let propfind = mock("PROPFIND", "/files")
+            .with_status(207)
+            .create();
+
+        let text = r#"head request {
+            url: text `<%base_url%>/health`
+        } matching headers { "X-Version": version }"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), mockito::server_url(), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        step.execute(&context).await.unwrap();
+        let (_, step) = RestSetp::parser(r#"method "PROPFIND" request { url: text `<%base_url%>/files` }"#).unwrap();
+        step.execute(&context).await.unwrap();
+        head.assert();
+        propfind.assert();
+        assert_eq!(context.get_var_from_store(format!("version")).await, Option::Some(Value::String("1.2".to_string())));
+        assert!(!context.has_failed());
+    }
+
+    #[tokio::test]
+    async fn should_send_form_body_with_its_content_type() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let mock = mock("POST", "/login")
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_body("user=atmaram&password=s3cret%21")
+            .with_status(200)
+            .create();
+
+        let text = r#"post request {
+            url: text `<%base_url%>/login`,
+            body: form { "user": "atmaram", "password": "s3cret!" }
+        }"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), mockito::server_url(), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        step.execute(&context).await.unwrap();
+        mock.assert();
+        assert!(!context.has_failed());
+    }
+
+    #[tokio::test]
+    async fn should_match_status_and_response_on_every_status() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let mock = mock("GET", "/missing")
+            .with_status(404)
+            .with_header("X-Trace", "t1")
+            .with_body(r#"{"error" : "not found" }"#)
+            .expect(2)
+            .create();
+
+        let text = r#"get request {
+            url: text `<%base_url%>/missing`
+        } matching status code matching response res matching body object { "error": error } expect status [2xx, 404]"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), mockito::server_url(), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        step.execute(&context).await.unwrap();
+        assert!(!context.has_failed());
+        assert_eq!(context.get_var_from_store(format!("code")).await, Option::Some(Value::PositiveInteger(404)));
+        assert_eq!(context.get_var_from_store(format!("error")).await, Option::Some(Value::String("not found".to_string())));
+        if let Some(Value::Map(res)) = context.get_var_from_store(format!("res")).await {
+            assert_eq!(res.get("status"), Option::Some(&Value::PositiveInteger(404)));
+            if let Some(Value::Map(headers)) = res.get("headers") {
+                assert_eq!(headers.get("x-trace"), Option::Some(&Value::String("t1".to_string())));
+            } else {
+                panic!("Expected headers in response")
+            }
+        } else {
+            panic!("Expected response to be matched")
+        }
+
+        let text = r#"get request {
+            url: text `<%base_url%>/missing`
+        } matching status code"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        step.execute(&context).await.unwrap();
+        mock.assert();
+        assert!(context.has_failed());
+    }
+
+    #[tokio::test]
+    async fn should_fetch_cache_and_send_oauth2_token() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let token = mock("POST", "/oauth/token")
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_body("grant_type=client_credentials&scope=read&client_id=corr&client_secret=s3cret")
+            .with_status(200)
+            .with_body(r#"{"access_token": "t1", "token_type": "bearer", "expires_in": 3600}"#)
+            .expect(1)
+            .create();
+        let api = mock("GET", "/secure")
+            .match_header("authorization", "Bearer t1")
+            .with_status(200)
+            .expect(2)
+            .create();
+
+        let text = r#"get request {
+            url: text `<%base_url%>/secure`,
+            auth: oauth2 { url: text `<%base_url%>/oauth/token`, clientId: "corr", clientSecret: "s3cret", scope: "read", cache: global }
+        }"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), mockito::server_url(), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        step.execute(&context).await.unwrap();
+        step.execute(&context).await.unwrap();
+        token.assert();
+        api.assert();
+        assert!(!context.has_failed());
+    }
+
+    #[tokio::test]
+    async fn should_time_out_hung_token_endpoint() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let stub = r#"listen on port with {
+            on post with url text `/oauth/token` {
+                wait 3000
+                let token.access_token = "t1"
+                respond with body token
+            }
+        }"#;
+        let (_, listener) = StartListenerStep::parser(stub).unwrap();
+        let server = Context::mock(vec![], Arc::new(Mutex::new(vec![])));
+        server.define("port".to_string(), Value::PositiveInteger(port as u128)).await;
+        listener.execute(&server).await.unwrap();
+        let text = r#"get request {
+            url: text `<%base_url%>/secure`,
+            timeout: 300ms,
+            auth: oauth2 { url: text `<%base_url%>/oauth/token`, clientId: "corr", cache: global }
+        }"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let context = Context::mock(vec![], Arc::new(Mutex::new(vec![])));
+        context.define("base_url".to_string(), Value::String(format!("http://127.0.0.1:{}", port))).await;
+        let started = std::time::Instant::now();
+        step.execute(&context).await.unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert_eq!(context.rest_stats_store.get_timeouts().await.len(), 1);
+        assert!(context.has_failed());
+    }
+
+    #[tokio::test]
+    async fn should_send_basic_auth_header() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let api = mock("GET", "/basic")
+            .match_header("authorization", "Basic dXNlcjpwYXNz")
+            .with_status(200)
+            .create();
+
+        let text = r#"get request {
+            url: text `<%base_url%>/basic`,
+            headers: { "Authorization": "ignored" },
+            auth: basic { username: "user", password: "pass" }
+        }"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), mockito::server_url(), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        step.execute(&context).await.unwrap();
+        api.assert();
+        assert!(!context.has_failed());
+    }
+
+    #[tokio::test]
+    async fn should_keep_cookies_in_jar_of_context() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let login = mock("POST", "/jar/login")
+            .with_status(200)
+            .with_header("Set-Cookie", "sid=xyz; Path=/jar")
+            .create();
+        let me = mock("GET", "/jar/me")
+            .match_header("cookie", "sid=xyz; theme=dark")
+            .with_status(200)
+            .create();
+
+        let input = vec![
+            Input::new_continue("base_url".to_string(), mockito::server_url(), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        let (_, step) = RestSetp::parser(r#"post request { url: text `<%base_url%>/jar/login` }"#).unwrap();
+        step.execute(&context).await.unwrap();
+        let (_, set) = CookieStep::parser(r#"set cookie "theme" = "dark" for base_url"#).unwrap();
+        set.execute(&context).await.unwrap();
+        let (_, step) = RestSetp::parser(r#"get request { url: text `<%base_url%>/jar/me` }"#).unwrap();
+        step.execute(&context).await.unwrap();
+        login.assert();
+        me.assert();
+        assert!(!context.has_failed());
+        let (_, sid) = Expression::parser(r#"cookie("sid")"#).unwrap();
+        assert_eq!(sid.evaluate(&context).await.unwrap(), Value::String("xyz".to_string()));
+        let (_, clear) = CookieStep::parser("clear cookies").unwrap();
+        clear.execute(&context).await.unwrap();
+        assert_eq!(sid.evaluate(&context).await.unwrap(), Value::Null);
+    }
+
+    #[tokio::test]
+    async fn should_follow_redirect_and_decompress_body() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, r#"{"id" : 7 }"#.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let created = mock("POST", "/moved/orders")
+            .with_status(302)
+            .with_header("Location", "/moved/orders/7")
+            .create();
+        let order = mock("GET", "/moved/orders/7")
+            .match_header("accept-encoding", mockito::Matcher::Regex("gzip".to_string()))
+            .with_status(200)
+            .with_header("Content-Encoding", "gzip")
+            .with_body(compressed)
+            .create();
+
+        let text = r#"post request {
+            url: text `<%base_url%>/moved/orders`,
+            body: object { "name" : "book" }
+        } matching body object { "id": id }"#;
+        let (_, step) = RestSetp::parser(text).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), mockito::server_url(), DataType::String)
+        ];
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context = Context::mock(input, buffer.clone());
+        step.execute(&context).await.unwrap();
+        created.assert();
+        order.assert();
+        assert!(!context.has_failed());
+        let timings = context.rest_stats_store.get_timings().await;
+        assert_eq!(timings.len(), 1);
+        assert!(timings[0].2.series().iter().any(|(series, _)| *series == "extraction_time"));
+        assert_eq!(context.get_var_from_store(format!("id")).await, Option::Some(Value::PositiveInteger(7)));
+    }
+    #[tokio::test]
+    async fn should_time_response_over_every_redirect_hop() {
+        if !can_bind_local_socket() {
+            return;
+        }
+        //Takes its time to redirect, then answers the next hop on the same connection at once
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            for answer in ["HTTP/1.1 302 Found\r\nLocation: /landed\r\nContent-Length: 0\r\n\r\n", "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"] {
+                let mut request = Vec::new();
+                while !request.ends_with(b"\r\n\r\n") {
+                    request.push(stream.read_u8().await.unwrap());
+                }
+                if answer.starts_with("HTTP/1.1 302") {
+                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                }
+                stream.write_all(answer.as_bytes()).await.unwrap();
+            }
+        });
+        let (_, step) = RestSetp::parser(r#"get request { url: base_url }"#).unwrap();
+        let input = vec![
+            Input::new_continue("base_url".to_string(), format!("http://{}/start", address), DataType::String)
+        ];
+        let context = Context::mock(input, Arc::new(Mutex::new(vec![])));
+        step.execute(&context).await.unwrap();
+        assert!(!context.has_failed());
+        let timings = context.rest_stats_store.get_timings().await;
+        assert!(timings[0].2.first_byte >= std::time::Duration::from_millis(200));
+        assert!(timings[0].2.dns.is_some());
+        let stats = context.rest_stats_store.get_stats().await;
+        assert_eq!(stats.len(), 1);
+        assert!(stats[0].2 >= 200);
+    }
 }
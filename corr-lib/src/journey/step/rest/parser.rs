@@ -1,34 +1,89 @@
 use crate::parser::{Parsable, ParseResult, ws};
 use crate::journey::step::rest::RestSetp;
-use nom::combinator::{map, opt};
-use nom::sequence::{tuple, preceded};
-use crate::template::rest::FillableRequest;
+use crate::journey::step::rest::cookie::CookieStep;
+use crate::template::Expression;
+use nom::branch::alt;
+use nom::character::complete::char;
+use nom::combinator::{map, opt, value};
+use nom::multi::{many0, separated_list1};
+use nom::sequence::{tuple, preceded, terminated};
+use crate::template::rest::{FillableRequest, StatusPattern};
+use crate::template::VariableReferenceName;
 use nom::bytes::complete::tag;
 use crate::template::rest::extractable::ExtractableRestData;
 
 
+enum ResponseOption{
+    Data(ExtractableRestData),
+    Status(VariableReferenceName),
+    Response(VariableReferenceName),
+    Expect(Vec<StatusPattern>)
+}
+fn response_option<'a>(input: &'a str) -> ParseResult<'a, ResponseOption> {
+    alt((
+        preceded(ws(tag("matching")),alt((
+            map(preceded(ws(tag("status")),ws(VariableReferenceName::parser)),ResponseOption::Status),
+            map(preceded(ws(tag("response")),ws(VariableReferenceName::parser)),ResponseOption::Response),
+            map(ExtractableRestData::parser,ResponseOption::Data),
+        ))),
+        map(preceded(tuple((ws(tag("expect")),ws(tag("status")))),alt((
+            preceded(ws(char('[')),terminated(separated_list1(ws(char(',')),ws(StatusPattern::parser)),ws(char(']')))),
+            map(ws(StatusPattern::parser),|pattern|vec![pattern])
+        ))),ResponseOption::Expect)
+    ))(input)
+}
 impl Parsable for RestSetp{
     fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
         map(
             tuple((
                 opt(ws(tag("async"))),
                 ws(FillableRequest::parser),
-                opt(preceded(ws(tag("matching")), ExtractableRestData::parser))
+                many0(response_option)
                 )),
-            |(ia,request,response)|RestSetp{
-                is_async:ia.map(|_| true).unwrap_or(false),
-                request,
-                response
+            |(ia,request,options)|{
+                let mut step = RestSetp{
+                    is_async:ia.map(|_| true).unwrap_or(false),
+                    request,
+                    response:Option::None,
+                    status:Option::None,
+                    full_response:Option::None,
+                    expect:vec![]
+                };
+                for option in options {
+                    match option {
+                        ResponseOption::Data(data)=>step.response = Option::Some(data),
+                        ResponseOption::Status(var)=>step.status = Option::Some(var),
+                        ResponseOption::Response(var)=>step.full_response = Option::Some(var),
+                        ResponseOption::Expect(patterns)=>step.expect = patterns,
+                    }
+                }
+                step
             }
         )(input)
     }
 }
+impl Parsable for CookieStep{
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        alt((
+            map(tuple((
+                ws(tag("set")),
+                ws(tag("cookie")),
+                ws(Expression::parser),
+                ws(char('=')),
+                ws(Expression::parser),
+                ws(tag("for")),
+                ws(Expression::parser)
+            )),|(_,_,name,_,value,_,url)|CookieStep::Set(name,value,url)),
+            value(CookieStep::Clear,tuple((ws(tag("clear")),ws(tag("cookies")))))
+        ))(input)
+    }
+}
 #[cfg(test)]
 mod tests{
     use crate::parser::Parsable;
     use crate::parser::util::assert_if;
     use crate::journey::step::rest::RestSetp;
-    use crate::template::rest::{FillableRequest, URL, RestVerb, FillableRequestHeaders, FillableRequestHeaderPair, FillableRequestHeaderValue};
+    use crate::template::rest::{FillableRequest, URL, RestVerb, FillableRequestHeaders, FillableRequestHeaderPair, FillableRequestHeaderValue, StatusPattern};
     use crate::template::{Expression, VariableReferenceName};
     use crate::core::Value;
     use crate::template::rest::extractable::{ExtractableRestData, ExtractableBody, ExtractableHeaders, ExtractableHeaderPair, ExtractableHeaderValue};
@@ -57,7 +112,12 @@ mod tests{
                             key:format!("X-API-KEY"),
                             value:FillableRequestHeaderValue::WithExpression(Expression::Variable(format!("x_api_key"),Option::None))
                         }]
-                    })
+                    }),
+                    query:Option::None,
+                    auth:Option::None,
+                    timeout:Option::None,
+                    retry:Option::None,
+                    redirects:Option::None
                 },
                 response:Option::Some(ExtractableRestData {
                     body:Option::Some(ExtractableBody::WithObject(ExtractableObject::WithMapObject(emo))),
@@ -67,7 +127,10 @@ mod tests{
                             value: ExtractableHeaderValue::WithVariableReference(VariableReferenceName::from("x_api_key"))
                         }]
                     })
-                })
+                }),
+                status:Option::None,
+                full_response:Option::None,
+                expect:vec![]
             })
 
     }
@@ -92,9 +155,44 @@ mod tests{
                             key:format!("X-API-KEY"),
                             value:FillableRequestHeaderValue::WithExpression(Expression::Variable(format!("x_api_key"),Option::None))
                         }]
-                    })
+                    }),
+                    query:Option::None,
+                    auth:Option::None,
+                    timeout:Option::None,
+                    retry:Option::None,
+                    redirects:Option::None
+                },
+                response:Option::None,
+                status:Option::None,
+                full_response:Option::None,
+                expect:vec![]
+            })
+
+    }
+    #[tokio::test]
+    async fn should_parse_reststep_with_status_response_and_expectation(){
+        let j= r#"get request {
+            url: "http://localhost"
+        } matching status code matching response res expect status [2xx, 404]"#;
+        assert_if(j
+                  ,RestSetp::parser(j)
+                  ,RestSetp{
+                is_async:false,
+                request:FillableRequest{
+                    url:URL::WithExpression(Expression::Constant(Value::String(format!("http://localhost")))),
+                    verb:RestVerb::GET,
+                    body:Option::None,
+                    headers:Option::None,
+                    query:Option::None,
+                    auth:Option::None,
+                    timeout:Option::None,
+                    retry:Option::None,
+                    redirects:Option::None
                 },
-                response:Option::None
+                response:Option::None,
+                status:Option::Some(VariableReferenceName::from("code")),
+                full_response:Option::Some(VariableReferenceName::from("res")),
+                expect:vec![StatusPattern::Class(2),StatusPattern::Exact(404)]
             })
 
     }
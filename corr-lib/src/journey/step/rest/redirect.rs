@@ -0,0 +1,97 @@
+use hyper::{Response, Body, StatusCode};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use crate::journey::step::rest::CorrRequest;
+use crate::template::rest::{RequestHeaders, RestVerb};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RedirectPolicy{
+    //Hops followed before the redirect itself is returned, 0 follows none
+    pub max:usize,
+    //Keep POST on 301 and 302 instead of switching to GET like browsers do
+    pub keep_method:bool,
+}
+impl Default for RedirectPolicy{
+    fn default()->Self{
+        RedirectPolicy{max:10,keep_method:false}
+    }
+}
+impl RedirectPolicy{
+    //Request to send for a redirect response, None when it should not be followed
+    pub fn follow(&self,request:&CorrRequest,response:&Response<Body>,hops:usize)->Option<CorrRequest>{
+        if hops >= self.max {
+            return Option::None;
+        }
+        let rewrite = match response.status() {
+            StatusCode::SEE_OTHER=>request.method != RestVerb::HEAD,
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND=>request.method == RestVerb::POST && !self.keep_method,
+            StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT=>false,
+            _=>return Option::None,
+        };
+        let location = response.headers().get(hyper::header::LOCATION)?.to_str().ok()?;
+        let from = Url::parse(request.url.as_str()).ok()?;
+        let to = from.join(location).ok()?;
+        let mut next = request.clone();
+        next.url = to.to_string();
+        if rewrite {
+            next.method = RestVerb::GET;
+            next.body = Option::None;
+        }
+        //Credentials set for one host are not handed to another
+        if from.host_str() != to.host_str() {
+            next.headers = request.headers.clone().map(|headers|RequestHeaders{
+                headers:headers.headers.into_iter()
+                    .filter(|header|!header.key.eq_ignore_ascii_case("authorization") && !header.key.eq_ignore_ascii_case("cookie"))
+                    .collect(),
+            });
+        }
+        Option::Some(next)
+    }
+}
+#[cfg(test)]
+mod tests{
+    use hyper::{Body, Response};
+    use crate::journey::step::rest::CorrRequest;
+    use crate::journey::step::rest::redirect::RedirectPolicy;
+    use crate::template::rest::{RequestBody, RequestHeader, RequestHeaders, RestVerb};
+    use crate::core::Value;
+
+    fn post()->CorrRequest{
+        CorrRequest{
+            method:RestVerb::POST,
+            url:"http://localhost/orders/new".to_string(),
+            body:Option::Some(RequestBody::JSON(Value::Null)),
+            headers:Option::Some(RequestHeaders { headers:vec![RequestHeader{key:"Authorization".to_string(),value:"Bearer t".to_string()}] }),
+            timeout:Option::None,
+            retry:Option::None,
+            redirects:Option::None,
+            oauth2:Option::None,
+        }
+    }
+    fn redirect(status:u16,location:&str)->Response<Body>{
+        Response::builder().status(status).header("Location",location).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn should_rewrite_method_as_per_status(){
+        let policy = RedirectPolicy::default();
+        let next = policy.follow(&post(),&redirect(302,"/orders/1"),0).unwrap();
+        assert_eq!(next.method,RestVerb::GET);
+        assert_eq!(next.body,Option::None);
+        assert_eq!(next.url,"http://localhost/orders/1");
+        let next = policy.follow(&post(),&redirect(307,"/orders/1"),0).unwrap();
+        assert_eq!(next.method,RestVerb::POST);
+        assert!(next.body.is_some());
+        let keep = RedirectPolicy{keep_method:true,..RedirectPolicy::default()};
+        assert_eq!(keep.follow(&post(),&redirect(301,"/orders/1"),0).unwrap().method,RestVerb::POST);
+        assert_eq!(keep.follow(&post(),&redirect(303,"/orders/1"),0).unwrap().method,RestVerb::GET);
+    }
+    #[test]
+    fn should_stop_after_max_hops_and_drop_credentials_across_hosts(){
+        let policy = RedirectPolicy{max:1,keep_method:false};
+        assert!(policy.follow(&post(),&redirect(302,"/orders/1"),1).is_none());
+        assert!(policy.follow(&post(),&redirect(304,"/orders/1"),0).is_none());
+        let next = policy.follow(&post(),&redirect(302,"https://auth.example.com/login"),0).unwrap();
+        assert_eq!(next.headers.unwrap().headers.len(),0);
+    }
+}
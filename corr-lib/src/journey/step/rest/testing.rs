@@ -0,0 +1,24 @@
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use hyper::{Body, Request, Response, Server};
+use hyper::service::{make_service_fn, service_fn};
+
+pub(crate) fn can_bind_local_socket()->bool{
+    TcpListener::bind("127.0.0.1:0").is_ok()
+}
+//Answers every request on a local port with what handler makes of it, callers check can_bind_local_socket first
+pub(crate) fn serve<H>(handler:H)->SocketAddr where H:Fn(Request<Body>)->Response<Body> + Clone + Send + Sync + 'static{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let make_svc = make_service_fn(move |_conn|{
+        let handler = handler.clone();
+        async move {
+            Ok::<_,Infallible>(service_fn(move |req:Request<Body>|{
+                let response = handler(req);
+                async move { Ok::<_,Infallible>(response) }
+            }))
+        }
+    });
+    tokio::spawn(Server::from_tcp(listener).unwrap().serve(make_svc));
+    address
+}
@@ -0,0 +1,205 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+use hyper::{Body, Response, Uri};
+use hyper::client::connect::{Connected, Connection};
+use hyper::service::Service;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+//Time spent resolving and connecting, carried by every response of the connection
+#[derive(Debug, Clone)]
+pub struct ConnectTiming{
+    dns:Duration,
+    connect:Duration,
+    claimed:Arc<AtomicBool>,
+}
+impl ConnectTiming{
+    pub fn new(dns:Duration,connect:Duration)->Self{
+        ConnectTiming{dns,connect,claimed:Arc::new(AtomicBool::new(false))}
+    }
+    //Only the first response on a connection paid for setting it up
+    fn claim(&self)->bool{
+        !self.claimed.swap(true,Ordering::SeqCst)
+    }
+}
+//Time from starting to connect until the connection, tls included, was ready
+#[derive(Debug, Clone, Copy)]
+struct Established(Duration);
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings{
+    //Connection phases are known only for requests that opened a connection
+    pub dns:Option<Duration>,
+    pub connect:Option<Duration>,
+    pub tls:Option<Duration>,
+    pub first_byte:Duration,
+    pub download:Duration,
+    pub extraction:Duration,
+    //Connections opened for the response, one per hop at most
+    pub opened:u64,
+}
+impl Timings{
+    //Phases up to the response headers, waiting being the time from sending the request
+    pub fn of(response:&Response<Body>,waiting:Duration,https:bool)->Self{
+        let extensions = response.extensions();
+        match (extensions.get::<ConnectTiming>(),extensions.get::<Established>()) {
+            (Some(connection),Some(Established(established))) if connection.claim()=>Timings {
+                dns:Option::Some(connection.dns),
+                connect:Option::Some(connection.connect),
+                tls:if https { Option::Some(established.saturating_sub(connection.dns + connection.connect)) } else { Option::None },
+                first_byte:waiting.saturating_sub(*established),
+                opened:1,
+                ..Timings::default()
+            },
+            _=>Timings{first_byte:waiting,..Timings::default()},
+        }
+    }
+    //Adds up the phases of another hop of the same response
+    pub fn add(&mut self,hop:&Timings){
+        let sum = |total:Option<Duration>,phase:Option<Duration>|match (total,phase) {
+            (Some(total),Some(phase))=>Option::Some(total + phase),
+            (total,phase)=>total.or(phase),
+        };
+        self.dns = sum(self.dns,hop.dns);
+        self.connect = sum(self.connect,hop.connect);
+        self.tls = sum(self.tls,hop.tls);
+        self.first_byte += hop.first_byte;
+        self.download += hop.download;
+        self.extraction += hop.extraction;
+        self.opened += hop.opened;
+    }
+    //Time from sending the request until its body was read, extraction left out
+    pub fn response_time(&self)->Duration{
+        self.dns.unwrap_or_default() + self.connect.unwrap_or_default() + self.tls.unwrap_or_default() + self.first_byte + self.download
+    }
+    pub fn series(&self)->Vec<(&'static str,Duration)>{
+        let mut series = vec![];
+        if let Some(dns) = self.dns {
+            series.push(("dns_lookup_time",dns));
+        }
+        if let Some(connect) = self.connect {
+            series.push(("tcp_connect_time",connect));
+        }
+        if let Some(tls) = self.tls {
+            series.push(("tls_handshake_time",tls));
+        }
+        series.push(("time_to_first_byte",self.first_byte));
+        series.push(("download_time",self.download));
+        series.push(("extraction_time",self.extraction));
+        series
+    }
+}
+//Times connections made by the connector it wraps, tls handshake included
+#[derive(Clone)]
+pub struct TimedConnector<C>{
+    inner:C,
+}
+impl<C> TimedConnector<C>{
+    pub fn new(inner:C)->Self{
+        TimedConnector{inner}
+    }
+}
+impl<C> Service<Uri> for TimedConnector<C>
+where
+    C:Service<Uri>,
+    C::Future:Send + 'static,
+    C::Error:Into<BoxError>,
+{
+    type Response = TimedStream<C::Response>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response,Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self,cx:&mut TaskContext<'_>)->Poll<Result<(),Self::Error>>{
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self,uri:Uri)->Self::Future{
+        let started = Instant::now();
+        let connecting = self.inner.call(uri);
+        Box::pin(async move {
+            let stream = connecting.await.map_err(Into::into)?;
+            Ok(TimedStream{inner:stream,established:started.elapsed()})
+        })
+    }
+}
+pub struct TimedStream<S>{
+    inner:S,
+    established:Duration,
+}
+impl<S:Connection> Connection for TimedStream<S>{
+    fn connected(&self)->Connected{
+        self.inner.connected().extra(Established(self.established))
+    }
+}
+impl<S:AsyncRead + Unpin> AsyncRead for TimedStream<S>{
+    fn poll_read(mut self:Pin<&mut Self>,cx:&mut TaskContext<'_>,buf:&mut ReadBuf<'_>)->Poll<io::Result<()>>{
+        Pin::new(&mut self.inner).poll_read(cx,buf)
+    }
+}
+impl<S:AsyncWrite + Unpin> AsyncWrite for TimedStream<S>{
+    fn poll_write(mut self:Pin<&mut Self>,cx:&mut TaskContext<'_>,buf:&[u8])->Poll<io::Result<usize>>{
+        Pin::new(&mut self.inner).poll_write(cx,buf)
+    }
+
+    fn poll_flush(mut self:Pin<&mut Self>,cx:&mut TaskContext<'_>)->Poll<io::Result<()>>{
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self:Pin<&mut Self>,cx:&mut TaskContext<'_>)->Poll<io::Result<()>>{
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+#[cfg(test)]
+mod tests{
+    use std::time::{Duration, Instant};
+    use hyper::{Body, Response};
+    use crate::journey::step::rest::client::{HttpClients, HttpConfig};
+    use crate::journey::step::rest::testing::{can_bind_local_socket, serve};
+    use crate::journey::step::rest::timing::{ConnectTiming, Established, Timings};
+
+    #[test]
+    fn should_charge_connection_phases_to_first_response_only(){
+        let connection = ConnectTiming::new(Duration::from_millis(5),Duration::from_millis(10));
+        let response = || {
+            let mut response = Response::new(Body::empty());
+            response.extensions_mut().insert(connection.clone());
+            response.extensions_mut().insert(Established(Duration::from_millis(40)));
+            response
+        };
+        let first = Timings::of(&response(),Duration::from_millis(100),true);
+        assert_eq!(first.dns,Option::Some(Duration::from_millis(5)));
+        assert_eq!(first.connect,Option::Some(Duration::from_millis(10)));
+        assert_eq!(first.tls,Option::Some(Duration::from_millis(25)));
+        assert_eq!(first.first_byte,Duration::from_millis(60));
+        let reused = Timings::of(&response(),Duration::from_millis(30),true);
+        assert_eq!(reused,Timings{first_byte:Duration::from_millis(30),..Timings::default()});
+        let mut redirected = first.clone();
+        redirected.add(&Timings{download:Duration::from_millis(2),..reused.clone()});
+        assert_eq!((redirected.dns,redirected.first_byte,redirected.download),(Option::Some(Duration::from_millis(5)),Duration::from_millis(90),Duration::from_millis(2)));
+        assert_eq!(redirected.response_time(),Duration::from_millis(132));
+        assert_eq!(reused.series().iter().map(|(name,_)| *name).collect::<Vec<&str>>(),vec!["time_to_first_byte","download_time","extraction_time"]);
+    }
+    #[tokio::test]
+    async fn should_time_connection_of_real_request(){
+        if !can_bind_local_socket() {
+            return;
+        }
+        let url = format!("http://localhost:{}/",serve(|_req|Response::new(Body::from("ok"))).port());
+        let clients = HttpClients::new(HttpConfig::default()).unwrap();
+        let mut timings = vec![];
+        for _ in 0..2 {
+            let sent = Instant::now();
+            let response = clients.client().get(url.parse().unwrap()).await.unwrap();
+            timings.push(Timings::of(&response,sent.elapsed(),false));
+            hyper::body::to_bytes(response.into_body()).await.unwrap();
+        }
+        assert!(timings[0].dns.is_some() && timings[0].connect.is_some());
+        assert_eq!(timings[0].tls,Option::None);
+        assert_eq!((timings[1].dns,timings[1].connect),(Option::None,Option::None));
+    }
+}
@@ -0,0 +1,212 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, bail, Result};
+use crate::journey::step::rest::client::TcpConnector;
+use crate::journey::step::rest::timing::TimedConnector;
+
+#[cfg(not(feature = "rustls"))]
+pub type HttpsConnector = TimedConnector<hyper_tls::HttpsConnector<TcpConnector>>;
+#[cfg(feature = "rustls")]
+pub type HttpsConnector = TimedConnector<hyper_rustls::HttpsConnector<TcpConnector>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TlsVersion{
+    Tls12,
+    Tls13,
+}
+impl FromStr for TlsVersion{
+    type Err = anyhow::Error;
+
+    fn from_str(s:&str)->Result<Self>{
+        match s {
+            "1.2"=>Ok(TlsVersion::Tls12),
+            "1.3"=>Ok(TlsVersion::Tls13),
+            _=>bail!("Expected TLS version to be 1.2 or 1.3"),
+        }
+    }
+}
+//Paths of PEM files, agents of a distributed run need them at the same paths
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig{
+    pub ca:Option<String>,
+    pub cert:Option<String>,
+    pub key:Option<String>,
+    pub insecure:bool,
+    pub min_version:Option<TlsVersion>,
+}
+impl TlsConfig{
+    fn identity(&self)->Result<Option<(Vec<u8>,Vec<u8>)>>{
+        match (&self.cert,&self.key) {
+            (Some(cert),Some(key))=>Ok(Option::Some((read(cert)?,read(key)?))),
+            (None,None)=>Ok(Option::None),
+            _=>bail!("Client certificate and key should be given together"),
+        }
+    }
+}
+fn read(path:&str)->Result<Vec<u8>>{
+    std::fs::read(path).map_err(|e|anyhow!("Unable to read {}: {}",path,e))
+}
+pub fn native_connector(config:&TlsConfig)->Result<native_tls::TlsConnector>{
+    use native_tls::{Certificate, Identity, Protocol, TlsConnector};
+    let mut builder = TlsConnector::builder();
+    if let Some(ca) = &config.ca {
+        for cert in Certificate::stack_from_pem(&read(ca)?)? {
+            builder.add_root_certificate(cert);
+        }
+    }
+    if let Some((cert,key)) = config.identity()? {
+        builder.identity(Identity::from_pkcs8(&cert,&key)?);
+    }
+    builder.danger_accept_invalid_certs(config.insecure);
+    builder.danger_accept_invalid_hostnames(config.insecure);
+    builder.min_protocol_version(config.min_version.map(|version|match version {
+        TlsVersion::Tls12=>Protocol::Tlsv12,
+        TlsVersion::Tls13=>Protocol::Tlsv13,
+    }));
+    Ok(builder.build()?)
+}
+#[cfg(not(feature = "rustls"))]
+pub fn https_connector(config:&TlsConfig,http:TcpConnector)->Result<HttpsConnector>{
+    Ok(TimedConnector::new(hyper_tls::HttpsConnector::from((http,native_connector(config)?.into()))))
+}
+#[cfg(feature = "rustls")]
+pub fn https_connector(config:&TlsConfig,http:TcpConnector)->Result<HttpsConnector>{
+    use std::sync::Arc;
+    use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore};
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs()? {
+        let _ = roots.add(&Certificate(cert.0));
+    }
+    if let Some(ca) = &config.ca {
+        for cert in rustls_pemfile::certs(&mut read(ca)?.as_slice())? {
+            roots.add(&Certificate(cert))?;
+        }
+    }
+    let versions:&[&rustls::SupportedProtocolVersion] = match config.min_version {
+        Some(TlsVersion::Tls13)=>&[&rustls::version::TLS13],
+        _=>rustls::ALL_VERSIONS,
+    };
+    let builder = ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(versions)?
+        .with_root_certificates(roots);
+    let mut tls = if let Some((cert,key)) = config.identity()? {
+        let certs = rustls_pemfile::certs(&mut cert.as_slice())?.into_iter().map(Certificate).collect();
+        let key = rustls_pemfile::pkcs8_private_keys(&mut key.as_slice())?.into_iter().next()
+            .ok_or_else(|| anyhow!("Client key should be a PKCS#8 PEM private key"))?;
+        builder.with_client_auth_cert(certs,PrivateKey(key))?
+    } else {
+        builder.with_no_client_auth()
+    };
+    if config.insecure {
+        tls.dangerous().set_certificate_verifier(Arc::new(AcceptAnyCertificate));
+    }
+    Ok(TimedConnector::new(hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(tls)
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(http)))
+}
+#[cfg(feature = "rustls")]
+struct AcceptAnyCertificate;
+#[cfg(feature = "rustls")]
+impl rustls::client::ServerCertVerifier for AcceptAnyCertificate{
+    fn verify_server_cert(
+        &self,
+        _end_entity:&rustls::Certificate,
+        _intermediates:&[rustls::Certificate],
+        _server_name:&rustls::ServerName,
+        _scts:&mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response:&[u8],
+        _now:std::time::SystemTime,
+    )->Result<rustls::client::ServerCertVerified,rustls::Error>{
+        Ok(rustls::client::ServerCertVerified::assertion())
+    }
+}
+#[cfg(test)]
+mod tests{
+    use std::convert::Infallible;
+    use hyper::{Body, Request, Response};
+    use hyper::server::conn::Http;
+    use hyper::service::service_fn;
+    use crate::journey::step::rest::client::{HttpClients, HttpConfig};
+    use crate::journey::step::rest::testing::can_bind_local_socket;
+    use crate::journey::step::rest::tls::TlsConfig;
+
+    const FIXTURES:&str = concat!(env!("CARGO_MANIFEST_DIR"),"/src/journey/step/rest/testdata");
+
+    fn fixture(name:&str)->String{
+        format!("{}/{}",FIXTURES,name)
+    }
+    //Serves https with a certificate for localhost signed by the test ca
+    async fn serve()->String{
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let identity = native_tls::Identity::from_pkcs8(
+            &std::fs::read(fixture("localhost.crt")).unwrap(),
+            &std::fs::read(fixture("localhost.key")).unwrap(),
+        ).unwrap();
+        let acceptor = tokio_native_tls::TlsAcceptor::from(native_tls::TlsAcceptor::new(identity).unwrap());
+        tokio::spawn(async move {
+            while let Ok((stream,_)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(stream) = acceptor.accept(stream).await {
+                        let service = service_fn(|_req:Request<Body>|async { Ok::<_,Infallible>(Response::new(Body::from("secure"))) });
+                        let _ = Http::new().serve_connection(stream,service).await;
+                    }
+                });
+            }
+        });
+        format!("https://localhost:{}/",port)
+    }
+    async fn get(url:&str,tls:TlsConfig)->anyhow::Result<String>{
+        let clients = HttpClients::new(HttpConfig{tls,..HttpConfig::default()})?;
+        let response = clients.client().get(url.parse()?).await?;
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        Ok(String::from_utf8(body.to_vec())?)
+    }
+
+    #[tokio::test]
+    async fn should_trust_server_only_with_custom_ca_or_insecure_mode(){
+        if !can_bind_local_socket() {
+            return;
+        }
+        let url = serve().await;
+        assert!(get(url.as_str(),TlsConfig::default()).await.is_err());
+        let trusted = TlsConfig{ca:Option::Some(fixture("ca.crt")),..TlsConfig::default()};
+        assert_eq!(get(url.as_str(),trusted).await.unwrap(),"secure");
+        let insecure = TlsConfig{insecure:true,..TlsConfig::default()};
+        assert_eq!(get(url.as_str(),insecure).await.unwrap(),"secure");
+    }
+    #[tokio::test]
+    async fn should_set_up_websocket_tls_from_same_configuration(){
+        if !can_bind_local_socket() {
+            return;
+        }
+        let url = serve().await;
+        let handshake = |tls:TlsConfig|{
+            let url = url.clone();
+            async move {
+                let clients = HttpClients::new(HttpConfig{tls,..HttpConfig::default()}).unwrap();
+                let stream = clients.connect(url.replace("https","wss").parse().unwrap()).await.unwrap();
+                tokio_native_tls::TlsConnector::from(clients.websocket_tls().clone()).connect("localhost",stream).await
+            }
+        };
+        assert!(handshake(TlsConfig::default()).await.is_err());
+        assert!(handshake(TlsConfig{ca:Option::Some(fixture("ca.crt")),..TlsConfig::default()}).await.is_ok());
+    }
+    #[test]
+    fn should_load_client_certificate_and_reject_it_without_key(){
+        let mtls = TlsConfig{
+            ca:Option::Some(fixture("ca.crt")),
+            cert:Option::Some(fixture("localhost.crt")),
+            key:Option::Some(fixture("localhost.key")),
+            ..TlsConfig::default()
+        };
+        assert!(HttpClients::new(HttpConfig{tls:mtls,..HttpConfig::default()}).is_ok());
+        let without_key = TlsConfig{cert:Option::Some(fixture("localhost.crt")),..TlsConfig::default()};
+        assert!(HttpClients::new(HttpConfig{tls:without_key,..HttpConfig::default()}).is_err());
+    }
+}
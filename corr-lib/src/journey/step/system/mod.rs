@@ -4,21 +4,23 @@ pub mod parser;
 
 use std::time::{Duration, Instant};
 use async_trait::async_trait;
-use num_traits::ToPrimitive;
 use crate::journey::{Executable};
 
 use crate::core::runtime::{Context, IO};
-use crate::core::{Number, Value};
+use crate::core::clock::waiting;
+use crate::core::{Number, Value, ThinkTime};
+use crate::core::feeder::{FeederDeclaration, current_vu};
 use crate::template::{VariableReferenceName, Fillable, Assignable, Expression};
 use crate::journey::step::Step;
 use tokio::task::JoinHandle;
 use tokio::fs::{OpenOptions};
 use tokio::io::{AsyncWriteExt};
-use tokio::time::sleep;
-use anyhow::Result;
+use anyhow::{bail, Result};
 #[derive(Debug, Clone,PartialEq)]
 pub enum SystemStep{
     Wait(WaitStep),
+    Think(ThinkStep),
+    Feed(FeederDeclaration),
     Exit(ExitStep),
     Print(PrintStep),
     ForLoop(ForLoopStep),
@@ -74,6 +76,11 @@ pub enum WaitStep{
     WithTime(Expression)
 }
 #[derive(Debug, Clone,PartialEq)]
+pub enum ThinkStep{
+    WithThinkTime(ThinkTime),
+    WithPacing(Duration)
+}
+#[derive(Debug, Clone,PartialEq)]
 pub enum ExitStep{
     WithCode(Expression)
 }
@@ -313,8 +320,8 @@ impl Executable for WaitStep {
     async fn execute(&self,context: &Context)->Result<Vec<JoinHandle<Result<bool>>>> {
         match &self {
             WaitStep::WithTime(time_exp)=>{
-                let wt = time_exp.evaluate(context).await?.to_number().unwrap_or(Number::Integer(128)).as_usize().unwrap_or(1);
-                sleep(Duration::from_millis(wt.to_u64().unwrap())).await;
+                let wt = time_exp.evaluate(context).await?.to_duration().unwrap_or(Duration::from_millis(1));
+                context.clock.sleep(wt).await;
                 return Ok(vec![]);
             }
         }
@@ -325,6 +332,28 @@ impl Executable for WaitStep {
     }
 }
 #[async_trait]
+impl Executable for ThinkStep {
+
+    async fn execute(&self,context: &Context)->Result<Vec<JoinHandle<Result<bool>>>> {
+        match &self {
+            ThinkStep::WithThinkTime(tt)=>{
+                context.clock.sleep(context.with_rng(|rng|tt.sample(rng))).await;
+            },
+            ThinkStep::WithPacing(pacing)=>{
+                let elapsed = context.elapsed();
+                if elapsed < *pacing {
+                    context.clock.sleep(*pacing - elapsed).await;
+                }
+            }
+        }
+        Ok(vec![])
+    }
+
+    fn get_deps(&self) -> Vec<String> {
+        vec![]
+    }
+}
+#[async_trait]
 impl Executable for ExitStep {
 
     async fn execute(&self,context: &Context)->Result<Vec<JoinHandle<Result<bool>>>> {
@@ -389,6 +418,18 @@ impl Executable for SystemStep{
             SystemStep::Wait(ws)=>{
                 ws.execute(context).await
             },
+            SystemStep::Think(ts)=>{
+                ts.execute(context).await
+            },
+            SystemStep::Feed(fd)=>{
+                let feeder = context.feeder_store.get_or_load(fd.path.clone(),fd.strategy).await?;
+                if let Some(record) = feeder.next(current_vu(context).await,context) {
+                    context.define(fd.variable.clone(),record).await;
+                    Ok(vec![])
+                } else {
+                    bail!("Feeder {} exhausted",fd.path)
+                }
+            },
             SystemStep::Exit(es)=>{
                 es.execute(context).await
             },
@@ -420,6 +461,7 @@ impl Executable for SystemStep{
             SystemStep::Transaction(tr)=>tr.execute(context).await,
             SystemStep::Metric(ms)=>ms.execute(context).await,
             SystemStep::Background(steps)=>{
+                let work = context.clock.work();
                 let context = context.clone();
                 let steps_to_pass = steps.clone();
                 let step = async move ||{
@@ -428,10 +470,10 @@ impl Executable for SystemStep{
                         let mut inner_handles = step.execute(&context).await?;
                         handles.append(&mut inner_handles);
                     }
-                    futures::future::join_all(handles).await;
+                    waiting(futures::future::join_all(handles)).await;
                     Ok(true)
                 };
-                Ok(vec![tokio::spawn(step())])
+                Ok(vec![work.spawn(step())])
             },
             SystemStep::JourneyStep(js)=>{
                 js.execute(context).await
@@ -449,6 +491,12 @@ impl Executable for SystemStep{
             SystemStep::Wait(ws)=>{
                 ws.get_deps()
             },
+            SystemStep::Think(ts)=>{
+                ts.get_deps()
+            },
+            SystemStep::Feed(_fd)=>{
+                vec![]
+            },
             SystemStep::Exit(es)=>{
                 es.get_deps()
             },
@@ -578,6 +626,7 @@ impl Executable for SyncStep {
 #[cfg(test)]
 mod tests{
     use crate::core::{DataType, Variable, Value, VariableValue};
+    use crate::core::clock::VirtualClock;
     use crate::core::proto::{Input, Output};
     use crate::journey::step::system::SystemStep;
     use std::sync::{Arc, Mutex};
@@ -585,6 +634,30 @@ mod tests{
     use crate::core::runtime::{Context, IO};
     use crate::parser::Parsable;
 
+    #[tokio::test]
+    async fn should_advance_virtual_clock_on_wait_step(){
+        let (_,step)=SystemStep::parser("wait 60000").unwrap();
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let context= Context::mock_with_clock(vec![],buffer.clone(),Arc::new(VirtualClock::new()));
+        let before = context.clock.now();
+        let started = std::time::Instant::now();
+        step.execute(&context).await.unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(context.clock.now().duration_since(before).unwrap(),std::time::Duration::from_secs(60));
+    }
+    #[tokio::test]
+    async fn should_pace_each_iteration_from_its_own_start(){
+        let (_,work)=SystemStep::parser("wait 300").unwrap();
+        let (_,pace)=SystemStep::parser("think pacing(1000)").unwrap();
+        let clock = Arc::new(VirtualClock::starting_at(std::time::UNIX_EPOCH));
+        let mut context= Context::mock_with_clock(vec![],Arc::new(Mutex::new(vec![])),clock.clone());
+        for iteration in 1..=2 {
+            context.start_iteration();
+            work.execute(&context).await.unwrap();
+            pace.execute(&context).await.unwrap();
+            assert_eq!(context.clock.now(),std::time::UNIX_EPOCH + std::time::Duration::from_millis(1000*iteration));
+        }
+    }
     #[tokio::test]
     async fn should_execute_print_step(){
         let text = r#"print text `Hello World`;"#;
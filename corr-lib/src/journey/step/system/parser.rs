@@ -1,5 +1,5 @@
 use crate::parser::{Parsable, ws};
-use crate::journey::step::system::{SystemStep, PrintStep, ForLoopStep, AssignmentStep, PushStep, ConditionalStep, IfPart, SyncStep, LoadAssignStep, JourneyStep, WaitStep, TransactionStep, MetricStep, WhileStep, ExitStep, RemoveStep};
+use crate::journey::step::system::{SystemStep, PrintStep, ForLoopStep, AssignmentStep, PushStep, ConditionalStep, IfPart, SyncStep, LoadAssignStep, JourneyStep, WaitStep, TransactionStep, MetricStep, WhileStep, ExitStep, RemoveStep, ThinkStep};
 use crate::parser::ParseResult;
 use nom::combinator::{map, opt};
 use nom::sequence::{delimited, preceded, terminated, tuple};
@@ -10,6 +10,10 @@ use crate::template::{VariableReferenceName, Assignable, Expression};
 use crate::journey::step::Step;
 use nom::multi::{many0, many1, separated_list0, separated_list1};
 use crate::journey::parser::parse_executable_name;
+use crate::core::ThinkTime;
+use crate::core::parser::duration_or;
+use std::time::Duration;
+use crate::core::feeder::FeederDeclaration;
 
 
 impl Parsable for WhileStep{
@@ -127,6 +131,8 @@ impl Parsable for SystemStep{
             // map(preceded(tag("//"),is_not("\n\r")),|val:&str|SystemStep::Comment(val.to_string())),
             // map(delimited(tag("/*"), is_not("*/"), tag("*/")),|val:&str|SystemStep::Comment(val.to_string())),
             map(WaitStep::parser,|ws|{SystemStep::Wait(ws)}),
+            map(ThinkStep::parser,|ts|{SystemStep::Think(ts)}),
+            map(preceded(ws(tag("feed")),FeederDeclaration::parser),|fd|{SystemStep::Feed(fd)}),
             map(ExitStep::parser,|ws|{SystemStep::Exit(ws)}),
             map(preceded(ws(tag("undef")),ws(VariableReferenceName::parser)),|vrn|{SystemStep::Undefine(vrn)}),
             map(TransactionStep::parser,|tr|{SystemStep::Transaction(tr)}),
@@ -155,6 +161,14 @@ impl Parsable for WaitStep {
         map(tuple((ws(tag("wait")),ws(Expression::parser))),|(_,wt)|{WaitStep::WithTime(wt)})(input)
     }
 }
+impl Parsable for ThinkStep {
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        preceded(ws(tag("think")),alt((
+            map(preceded(ws(tag("pacing")),delimited(ws(char('(')),ws(duration_or(Duration::from_millis)),ws(char(')')))),ThinkStep::WithPacing),
+            map(ThinkTime::parser,ThinkStep::WithThinkTime)
+        )))(input)
+    }
+}
 impl Parsable for ExitStep {
     fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
         map(tuple((ws(tag("exit")),ws(Expression::parser))),|(_,wt)|{ExitStep::WithCode(wt)})(input)
@@ -215,7 +229,9 @@ impl Parsable for ConditionalStep{
 
 #[cfg(test)]
 mod tests{
-    use crate::journey::step::system::{SystemStep, PrintStep, ForLoopStep, AssignmentStep, PushStep, JourneyStep};
+    use crate::journey::step::system::{SystemStep, PrintStep, ForLoopStep, AssignmentStep, PushStep, JourneyStep, ThinkStep};
+    use crate::core::ThinkTime;
+    use std::time::Duration;
     use crate::parser::Parsable;
     use crate::template::text::{Text, Block};
     use crate::parser::util::{assert_if, assert_no_error};
@@ -223,6 +239,18 @@ mod tests{
     use crate::journey::step::Step;
     use crate::journey::step::system::parser::{one_or_many_steps, unarged_for_parser, for_right_part, for_left_part, arged_for_parser};
 
+    #[tokio::test]
+    async fn should_parse_think_step(){
+        let j= r#"think normal(1000,200)"#;
+        assert_if(j
+                  ,SystemStep::parser(j)
+                  ,SystemStep::Think(ThinkStep::WithThinkTime(ThinkTime::Normal(Duration::from_millis(1000),Duration::from_millis(200)))));
+        let j= r#"think pacing(5s)"#;
+        assert_if(j
+                  ,SystemStep::parser(j)
+                  ,SystemStep::Think(ThinkStep::WithPacing(Duration::from_secs(5))))
+
+    }
     #[tokio::test]
     async fn should_parse_for_left_part(){
         let j= r#"atmaram.naik.for"#;
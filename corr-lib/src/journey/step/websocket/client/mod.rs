@@ -7,9 +7,8 @@ use crate::core::runtime::Context;
 use crate::journey::{Executable};
 use crate::template::{Expression, Fillable, VariableReferenceName};
 use async_trait::async_trait;
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use hyper::header::HeaderValue;
-use hyper_tls::native_tls::TlsConnector;
 use tokio::time::Instant;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::http::header::HeaderName;
@@ -61,11 +60,15 @@ impl Executable for WebSocketClientConnectStep {
             }
         }
         let start = Instant::now();
-        let conn=if url.starts_with("wss") {
-            tokio_tungstenite::connect_async_tls_with_config(req, Some(WebSocketConfig::default()), false,Some(Connector::NativeTls(TlsConnector::builder().danger_accept_invalid_certs(true).build()?))).await
+        let connector = if url.starts_with("wss") {
+            Some(Connector::NativeTls(context.http.websocket_tls().clone()))
         } else {
-            tokio_tungstenite::connect_async_with_config(req,Some(WebSocketConfig::default()),false).await
-        };//;
+            None
+        };
+        let conn = match context.http.connect(req.uri().to_string().parse()?).await {
+            Ok(stream)=>tokio_tungstenite::client_async_tls_with_config(req,stream,Some(WebSocketConfig::default()),connector).await.map_err(|e|anyhow!(e)),
+            Err(e)=>Err(e)
+        };
         let duration = start.elapsed();
         context.scrapper.ingest("connection_time",duration.as_millis() as f64,vec![(format!("name"),name.clone())]).await;
         match conn {
@@ -23,6 +23,8 @@ pub fn get_journey_keywords<'a>()->Vec<&'a str>{
         "ingest",
         "as",
         "wait",
+        "think",
+        "feed",
         "exit",
         "print",
                 "respond",
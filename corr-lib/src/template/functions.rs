@@ -11,7 +11,8 @@ use fake::locales::*;
 use base64::encode;
 use fake::Fake;
 use rand::Rng;
-use std::time::{SystemTime, UNIX_EPOCH};
+use rand::rngs::StdRng;
+use std::time::{UNIX_EPOCH};
 use strfmt::{ Formatter, strfmt_map};
 use std::collections::HashMap;
 use std::env;
@@ -77,6 +78,8 @@ pub struct CorrCaptcha;
 //Concat Function
 #[derive(Debug,Clone,PartialEq)]
 pub struct EnvVar;
+#[derive(Debug,Clone,PartialEq)]
+pub struct CookieValue;
 
 //Concat Function
 #[derive(Debug,Clone,PartialEq)]
@@ -164,21 +167,23 @@ impl Function for UniqueRandomElements{
                 } else {
                     vec![]
                 };
-                let mut vals = vec![];
-                let mut to_add;
-                let mut rng = rand::thread_rng();
-                for i in 0..total {
-                    if i>=array.len(){
-                        break
-                    }
-                    let index = rng.gen_range(0,array.len() -1 );
-                    to_add =  array[index].clone();
-                    while vals.contains(&to_add) {
-                        let index = rng.gen_range(0,array.len() -1 );
-                        to_add =  array[index].clone()
+                let vals = context.with_rng(|rng|{
+                    let mut vals = vec![];
+                    let mut to_add;
+                    for i in 0..total {
+                        if i>=array.len(){
+                            break
+                        }
+                        let index = rng.gen_range(0..array.len() -1 );
+                        to_add =  array[index].clone();
+                        while vals.contains(&to_add) {
+                            let index = rng.gen_range(0..array.len() -1 );
+                            to_add =  array[index].clone()
+                        }
+                        vals.push(to_add.clone());
                     }
-                    vals.push(to_add.clone());
-                }
+                    vals
+                });
                 Ok(Value::Array(vals))
             } else {
                 Ok(Value::Array(vec![]))
@@ -290,6 +295,18 @@ impl Function for EnvVar{
         Ok(env::var(args.get(0).unwrap().evaluate(context).await?.to_string()).map(|e|Value::String(e)).unwrap_or(Value::Null))
     }
 }
+#[async_trait]
+impl Function for CookieValue{
+    async fn evaluate(&self, args: Vec<Expression>, context: &Context) -> Result<Value> {
+        let name = args.first().unwrap_or(&Expression::Constant(Value::Null)).evaluate(context).await?.to_string();
+        let url = if let Some(url) = args.get(1) {
+            Option::Some(url.evaluate(context).await?.to_string())
+        } else {
+            Option::None
+        };
+        Ok(context.http.cookies().get(name.as_str(),url.as_deref(),context.clock.now()).map(Value::String).unwrap_or(Value::Null))
+    }
+}
 //Add Function
 #[derive(Debug,Clone,PartialEq)]
 pub struct Add;
@@ -633,15 +650,16 @@ impl Function for Round{
 
 #[async_trait]
 impl Function for Uuid{
-    async fn evaluate(&self, _args: Vec<Expression>, _context: &Context) -> Result<Value> {
-        let val = uuid::Uuid::new_v4();
+    async fn evaluate(&self, _args: Vec<Expression>, context: &Context) -> Result<Value> {
+        let bytes:[u8;16] = context.with_rng(|rng|rng.gen());
+        let val = uuid::Builder::from_bytes(bytes).set_variant(uuid::Variant::RFC4122).set_version(uuid::Version::Random).build();
         Ok(Value::String(val.to_string()))
     }
 }
 #[async_trait]
 impl Function for TimeStamp{
-    async fn evaluate(&self, _args: Vec<Expression>, _context: &Context) -> Result<Value> {
-        let val = SystemTime::now()
+    async fn evaluate(&self, _args: Vec<Expression>, context: &Context) -> Result<Value> {
+        let val = context.clock.now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_millis();
@@ -654,9 +672,9 @@ impl Function for Now{
     async fn evaluate(&self, args: Vec<Expression>, context: &Context) -> Result<Value> {
         let  value = if args.len() == 1 {
             let format = args.get(0).unwrap().evaluate(context).await?.to_string();
-            chrono::Utc::now().format(format.as_str()).to_string()
+            chrono::DateTime::<chrono::Utc>::from(context.clock.now()).format(format.as_str()).to_string()
         } else {
-            chrono::Utc::now().to_rfc3339().to_string()
+            chrono::DateTime::<chrono::Utc>::from(context.clock.now()).to_rfc3339().to_string()
         };
         Ok(Value::String(value))
     }
@@ -732,7 +750,7 @@ impl Function for IndexOf{
 impl Function for FakeValue{
     async fn evaluate(&self, args: Vec<Expression>, context: &Context) -> Result<Value> {
         if let Value::String(arg) = args.get(0).unwrap().fill(context).await? {
-            Ok(get_fake(arg))
+            Ok(context.with_rng(|rng|get_fake(arg,rng)))
         } else {
             Ok(Value::Null)
         }
@@ -814,22 +832,22 @@ impl Function for Formated{
         }
     }
 }
-fn get_fake(fake_type:String)->Value{
+fn get_fake(fake_type:String,rng:&mut StdRng)->Value{
     match fake_type.as_str() {
-        "Name"=> Value::String(Name(EN).fake()),
-        "FirstName"=>Value::String(FirstName(EN).fake()),
-        "LastName"=>Value::String(LastName(EN).fake()),
-        "Title"=>Value::String(Title(EN).fake()),
-        "Suffix"=>Value::String(Suffix(EN).fake()),
-        "NameWithTitle"=>Value::String(NameWithTitle(EN).fake()),
-        "CompanySuffix"=>Value::String(CompanySuffix(EN).fake()),
-        "CompanyName"=>Value::String(CompanyName(EN).fake()),
-        "Profession"=>Value::String(Profession(EN).fake()),
-        "CityName"=>Value::String(CityName(EN).fake()),
-        "StreetName"=>Value::String(StreetName(EN).fake()),
-        "StateName"=>Value::String(StateName(EN).fake()),
-        "StateAbbr"=>Value::String(StateAbbr(EN).fake()),
-        "ZipCode"=>Value::String(format!("{:05}",ZipCode(EN).fake::<String>().trim())),
+        "Name"=> Value::String(Name(EN).fake_with_rng::<String,_>(rng)),
+        "FirstName"=>Value::String(FirstName(EN).fake_with_rng::<String,_>(rng)),
+        "LastName"=>Value::String(LastName(EN).fake_with_rng::<String,_>(rng)),
+        "Title"=>Value::String(Title(EN).fake_with_rng::<String,_>(rng)),
+        "Suffix"=>Value::String(Suffix(EN).fake_with_rng::<String,_>(rng)),
+        "NameWithTitle"=>Value::String(NameWithTitle(EN).fake_with_rng::<String,_>(rng)),
+        "CompanySuffix"=>Value::String(CompanySuffix(EN).fake_with_rng::<String,_>(rng)),
+        "CompanyName"=>Value::String(CompanyName(EN).fake_with_rng::<String,_>(rng)),
+        "Profession"=>Value::String(Profession(EN).fake_with_rng::<String,_>(rng)),
+        "CityName"=>Value::String(CityName(EN).fake_with_rng::<String,_>(rng)),
+        "StreetName"=>Value::String(StreetName(EN).fake_with_rng::<String,_>(rng)),
+        "StateName"=>Value::String(StateName(EN).fake_with_rng::<String,_>(rng)),
+        "StateAbbr"=>Value::String(StateAbbr(EN).fake_with_rng::<String,_>(rng)),
+        "ZipCode"=>Value::String(format!("{:05}",ZipCode(EN).fake_with_rng::<String,_>(rng).trim())),
         _=>Value::Null
     }
 }
@@ -865,30 +883,29 @@ impl Function for Random{
 
                 let value1:Value = val1.fill(context).await?;
                 let value2:Value = val2.fill(context).await?;
-                let mut rng = rand::thread_rng();
-                if let Value::PositiveInteger(uv1) = value1{
+                context.with_rng(|rng|if let Value::PositiveInteger(uv1) = value1{
                     if let Value::PositiveInteger(uv2)=value2{
 
-                        Ok(Value::PositiveInteger(rng.gen_range(uv1,uv2)))
+                        Ok(Value::PositiveInteger(rng.gen_range(uv1..uv2)))
                     } else {
                         Ok(Value::Null)
                     }
                 } else if let Value::Integer(uv1) = value1{
                     if let Value::Integer(uv2)=value2{
 
-                        Ok(Value::Integer(rng.gen_range(uv1,uv2)))
+                        Ok(Value::Integer(rng.gen_range(uv1..uv2)))
                     } else {
                         Ok(Value::Null)
                     }
                 } else if let Value::Double(uv1) = value1{
                     if let Value::Double(uv2)=value2{
-                        Ok(Value::Double(rng.gen_range(uv1,uv2)))
+                        Ok(Value::Double(rng.gen_range(uv1..uv2)))
                     } else {
                         Ok(Value::Null)
                     }
                 } else {
                     Ok(Value::Null)
-                }
+                })
             } else {
                 Ok(Value::Null)
             }
@@ -909,8 +926,7 @@ impl Function for RandomElement{
                 } else if val.len() == 1 {
                     Ok(val.get(0).unwrap().clone())
                 } else {
-                    let mut rng = rand::thread_rng();
-                    let index = rng.gen_range(0,val.len() -1 );
+                    let index = context.with_rng(|rng|rng.gen_range(0..val.len() -1 ));
                     if let Some(ret_val) = val.get(index){
                         Ok(ret_val.clone())
                     } else {
@@ -1074,7 +1090,8 @@ pub fn functions()->Vec<(&'static str,Arc<dyn Function>)>{
         ("unique_random_elements",Arc::new(UniqueRandomElements{})),
         ("contains",Arc::new(Contains{})),
         ("random_element",Arc::new(RandomElement{})),
-        ("env",Arc::new(EnvVar{}))
+        ("env",Arc::new(EnvVar{})),
+        ("cookie",Arc::new(CookieValue{}))
     ]
 }
 pub fn function_names()->Vec<&'static str>{
@@ -1101,6 +1118,8 @@ mod tests{
     use crate::template::functions::*;
     use crate::core::runtime::Context;
     use crate::template::{Expression, Function};
+    use rand::SeedableRng;
+    use crate::core::clock::VirtualClock;
 
     #[tokio::test]
     async fn should_find_index_of_array(){
@@ -1431,8 +1450,28 @@ mod tests{
         assert_eq!(result,Value::Boolean(false));
     }
     #[tokio::test]
+    async fn should_generate_same_uuid_and_fake_value_for_same_seed(){
+        let mut context=Context::mock(vec![],Arc::new(Mutex::new(vec![])));
+        context.set_seed(7);
+        let first = (Uuid{}.evaluate(vec![],&context).await.unwrap(),FakeValue{}.evaluate(vec![Expression::Constant(Value::String("Name".to_string()))],&context).await.unwrap());
+        context.reseed().await;
+        let second = (Uuid{}.evaluate(vec![],&context).await.unwrap(),FakeValue{}.evaluate(vec![Expression::Constant(Value::String("Name".to_string()))],&context).await.unwrap());
+        context.set_seed(7);
+        let third = (Uuid{}.evaluate(vec![],&context).await.unwrap(),FakeValue{}.evaluate(vec![Expression::Constant(Value::String("Name".to_string()))],&context).await.unwrap());
+        assert_ne!(first,second);
+        assert_eq!(first,third);
+    }
+    #[tokio::test]
+    async fn should_read_time_from_context_clock(){
+        let clock = Arc::new(VirtualClock::starting_at(UNIX_EPOCH));
+        let context=Context::mock_with_clock(vec![],Arc::new(Mutex::new(vec![])),clock.clone());
+        clock.advance(std::time::Duration::from_secs(60));
+        assert_eq!(TimeStamp{}.evaluate(vec![],&context).await.unwrap(),Value::PositiveInteger(60000));
+        assert_eq!(Now{}.evaluate(vec![],&context).await.unwrap(),Value::String("1970-01-01T00:01:00+00:00".to_string()));
+    }
+    #[tokio::test]
     async fn should_get_zipcode(){
-        if let Value::String(str)=get_fake("Zipcode".to_string()){
+        if let Value::String(str)=get_fake("Zipcode".to_string(),&mut StdRng::seed_from_u64(1)){
             assert_eq!(str.len(),5);
         }
 
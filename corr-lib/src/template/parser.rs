@@ -201,6 +201,7 @@ impl Parsable for FunctionCallChain {
 #[cfg(test)]
 mod tests{
     use crate::parser::util::{assert_if, assert_no_error};
+    use std::time::Duration;
     use crate::parser::Parsable;
     use crate::template::{Expression, VariableReferenceName, Assignable, Operator, BinaryOperator, FunctionCallChain};
     use crate::core::{Value};
@@ -322,6 +323,17 @@ mod tests{
         assert_if(text,a,Expression::Constant(Value::String("Atmaram".to_string())))
     }
 
+    #[test]
+    fn should_parse_duration_as_function_argument(){
+        let text=r#"concat("timeout ",30s)"#;
+        let a=Expression::parser(text);
+        assert_if(text,a,
+                  Expression::Function("concat".to_string(),vec![
+                      Expression::Constant(Value::String("timeout ".to_string())),
+                      Expression::Constant(Value::Duration(Duration::from_secs(30)))
+                  ])
+        )
+    }
     #[test]
     fn should_parse_expression_when_operator(){
         let text=r#"100 + 15 / 10"#;
@@ -7,24 +7,48 @@ use async_trait::async_trait;
 use crate::template::object::FillableObject;
 use crate::core::Value;
 use crate::journey::step::rest::CorrRequest;
+use crate::journey::step::rest::auth::{OAuth2Request, TokenScope};
+use crate::journey::step::rest::redirect::RedirectPolicy;
 use multer::bytes::Bytes;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+use rand::Rng;
+use url::Url;
+use std::time::Duration;
 #[derive(Debug, Clone,PartialEq)]
 pub enum RestVerb{
     GET,
     POST,
     PUT,
     PATCH,
-    DELETE
+    DELETE,
+    HEAD,
+    OPTIONS,
+    TRACE,
+    CONNECT,
+    //Any other method, kept exactly as written since methods are case sensitive
+    Custom(String)
 }
 impl RestVerb {
-    pub fn as_str(&self)->&'static str{
+    pub fn as_str(&self)->&str{
         match self {
             RestVerb::GET=>"get",
             RestVerb::POST=>"post",
             RestVerb::PUT=>"put",
             RestVerb::PATCH=>"patch",
             RestVerb::DELETE=>"delete",
+            RestVerb::HEAD=>"head",
+            RestVerb::OPTIONS=>"options",
+            RestVerb::TRACE=>"trace",
+            RestVerb::CONNECT=>"connect",
+            RestVerb::Custom(method)=>method.as_str(),
+        }
+    }
+    //Method as sent on the wire
+    pub fn as_method(&self)->String{
+        match self {
+            RestVerb::Custom(method)=>method.clone(),
+            _=>self.as_str().to_uppercase()
         }
     }
 }
@@ -34,11 +58,76 @@ pub struct FillableRequest {
     pub url: URL,
     pub body:Option<FillableRequestBody>,
     pub headers:Option<FillableRequestHeaders>,
+    pub query:Option<Vec<FillableFormField>>,
+    pub auth:Option<FillableAuth>,
+    pub timeout:Option<Duration>,
+    pub retry:Option<RetryPolicy>,
+    pub redirects:Option<RedirectPolicy>,
+}
+#[derive(Debug, Clone,PartialEq)]
+pub enum RetryOn{
+    Status(u16),
+    Timeout,
+    Error
+}
+#[derive(Debug, Clone,PartialEq)]
+pub struct RetryPolicy{
+    pub times:u64,
+    pub backoff:Duration,
+    pub on:Vec<RetryOn>
+}
+impl RetryPolicy {
+    //Backoff is the same before every attempt
+    pub fn backoff_for(&self,_attempt:u64)->Duration{
+        self.backoff
+    }
+    pub fn should_retry(&self,attempt:u64,outcome:&RetryOn)->bool{
+        attempt < self.times && self.on.contains(outcome)
+    }
+}
+#[derive(Debug, Clone,PartialEq)]
+pub enum FillableAuth{
+    Basic(Expression,Expression),
+    Bearer(Expression),
+    OAuth2(Box<FillableOAuth2>)
+}
+#[derive(Debug, Clone,PartialEq)]
+pub struct FillableOAuth2{
+    pub url:URL,
+    pub client_id:Expression,
+    pub client_secret:Option<Expression>,
+    pub scope:Option<Expression>,
+    pub username:Option<Expression>,
+    pub password:Option<Expression>,
+    pub cache:TokenScope
+}
+#[derive(Debug, Clone,PartialEq)]
+pub enum StatusPattern{
+    Exact(u16),
+    //Any status of a class like 2xx
+    Class(u16)
+}
+impl StatusPattern {
+    pub fn matches(&self,status:u16)->bool{
+        match self {
+            StatusPattern::Exact(expected)=>*expected == status,
+            StatusPattern::Class(class)=>status / 100 == *class
+        }
+    }
+    //Without expectations anything below 400 is a success
+    pub fn is_expected(patterns:&[StatusPattern],status:u16)->bool{
+        if patterns.is_empty() {
+            status < 400
+        } else {
+            patterns.iter().any(|pattern|pattern.matches(status))
+        }
+    }
 }
 #[derive(Debug, Clone,PartialEq)]
 pub struct FillableRequestHeaders{
     pub headers:Vec<FillableRequestHeaderPair>
 }
+#[derive(Debug, Clone,PartialEq)]
 pub struct MultipartField{
     pub name:Option<String>,
     pub file_name:Option<String>,
@@ -70,11 +159,31 @@ pub enum FillableRequestHeaderValue{
 }
 #[derive(Debug, Clone,PartialEq)]
 pub enum FillableRequestBody{
-    WithObject(FillableObject)
+    WithObject(FillableObject),
+    WithForm(Vec<FillableFormField>),
+    WithMultipart(Vec<FillableFormField>),
+    WithText(Text),
+    WithBinary(Expression)
+}
+#[derive(Debug, Clone,PartialEq)]
+pub struct FillableFormField{
+    pub name:String,
+    pub value:FillableFormValue
+}
+#[derive(Debug, Clone,PartialEq)]
+pub enum FillableFormValue{
+    WithExpression(Expression),
+    WithText(Text),
+    //Path of a file to upload, only in multipart bodies
+    File(Expression)
 }
 #[derive(Debug, Clone,PartialEq)]
 pub enum RequestBody{
-    JSON(Value)
+    JSON(Value),
+    Form(Vec<(String,String)>),
+    Multipart(String,Vec<MultipartField>),
+    Text(String),
+    Binary(Vec<u8>)
 }
 #[derive(Debug, Clone,PartialEq)]
 pub enum URL{
@@ -82,6 +191,40 @@ pub enum URL{
     WithText(Text)
 }
 impl RequestBody{
+    pub fn content_type(&self)->String{
+        match self {
+            RequestBody::JSON(_)=>"application/json".to_string(),
+            RequestBody::Form(_)=>"application/x-www-form-urlencoded".to_string(),
+            RequestBody::Multipart(boundary,_)=>format!("multipart/form-data; boundary={}",boundary),
+            RequestBody::Text(_)=>"text/plain; charset=utf-8".to_string(),
+            RequestBody::Binary(_)=>"application/octet-stream".to_string(),
+        }
+    }
+    pub fn to_bytes(&self)->Vec<u8>{
+        match self {
+            RequestBody::Form(fields)=>serde_urlencoded::to_string(fields).unwrap_or("".to_string()).into_bytes(),
+            RequestBody::Multipart(boundary,fields)=>{
+                let mut bytes = vec![];
+                for field in fields {
+                    bytes.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"",boundary,field.name.clone().unwrap_or_default()).as_bytes());
+                    if let Some(file_name) = &field.file_name {
+                        bytes.extend_from_slice(format!("; filename=\"{}\"",file_name).as_bytes());
+                    }
+                    if let Some(content_type) = &field.content_type {
+                        bytes.extend_from_slice(format!("\r\nContent-Type: {}",content_type).as_bytes());
+                    }
+                    bytes.extend_from_slice(b"\r\n\r\n");
+                    bytes.extend_from_slice(field.contents.clone().unwrap_or_default().as_ref());
+                    bytes.extend_from_slice(b"\r\n");
+                }
+                bytes.extend_from_slice(format!("--{}--\r\n",boundary).as_bytes());
+                bytes
+            },
+            RequestBody::Text(text)=>text.clone().into_bytes(),
+            RequestBody::Binary(data)=>data.clone(),
+            RequestBody::JSON(_)=>self.to_string_body().into_bytes()
+        }
+    }
     pub fn to_string_body(&self)->String{
         match self {
             RequestBody::JSON(val)=>{
@@ -90,10 +233,34 @@ impl RequestBody{
                 } else {
                     "".to_string()
                 }
-            }
+            },
+            _=>String::from_utf8_lossy(&self.to_bytes()).to_string()
         }
     }
 }
+//Appends encoded query pairs to the url, keeping the ones it already has
+fn with_query(url:&str,pairs:Vec<(String,String)>)->Result<String>{
+    let mut url = Url::parse(url).map_err(|e|anyhow!("Invalid url {}: {}",url,e))?;
+    url.query_pairs_mut().extend_pairs(pairs);
+    Ok(url.to_string())
+}
+fn content_type_of(path:&str)->&'static str{
+    match Path::new(path).extension().and_then(|ext|ext.to_str()).map(|ext|ext.to_lowercase()).as_deref() {
+        Some("png")=>"image/png",
+        Some("jpg") | Some("jpeg")=>"image/jpeg",
+        Some("gif")=>"image/gif",
+        Some("svg")=>"image/svg+xml",
+        Some("pdf")=>"application/pdf",
+        Some("json")=>"application/json",
+        Some("xml")=>"application/xml",
+        Some("zip")=>"application/zip",
+        Some("wav")=>"audio/wav",
+        Some("txt")=>"text/plain",
+        Some("csv")=>"text/csv",
+        Some("html")=>"text/html",
+        _=>"application/octet-stream"
+    }
+}
 #[async_trait]
 impl Fillable<CorrRequest> for FillableRequest{
     async fn fill(&self, context: &Context) -> Result<CorrRequest> {
@@ -102,19 +269,96 @@ impl Fillable<CorrRequest> for FillableRequest{
         } else {
             Option::None
         };
-        let headers = if let Some(frh)=&self.headers{
+        let mut headers = if let Some(frh)=&self.headers{
             Option::Some(frh.fill(context).await?)
         } else {
             Option::None
         };
+        let oauth2 = match &self.auth {
+            Some(FillableAuth::OAuth2(oauth))=>Option::Some(oauth.fill(context).await?),
+            Some(auth)=>{
+                let authorization = auth.fill(context).await?;
+                let mut filled = headers.map(|h|h.headers).unwrap_or_default();
+                filled.retain(|header|!header.key.eq_ignore_ascii_case(authorization.key.as_str()));
+                filled.push(authorization);
+                headers = Option::Some(RequestHeaders{headers:filled});
+                Option::None
+            },
+            None=>Option::None
+        };
+        let mut url = self.url.fill(context).await?;
+        if let Some(query) = &self.query {
+            let mut pairs = vec![];
+            for field in query {
+                match &field.value {
+                    FillableFormValue::WithExpression(expr)=>match expr.evaluate(context).await? {
+                        Value::Array(values)=>{
+                            for value in values {
+                                pairs.push((field.name.clone(),value.to_string()));
+                            }
+                        },
+                        value=>pairs.push((field.name.clone(),value.to_string()))
+                    },
+                    FillableFormValue::WithText(text)=>pairs.push((field.name.clone(),text.fill(context).await?)),
+                    FillableFormValue::File(_)=>bail!("Files can only be sent in a multipart body")
+                }
+            }
+            url = with_query(url.as_str(),pairs)?;
+        }
         Ok(CorrRequest {
             method: self.verb.clone(),
             body,
-            url: self.url.fill(context).await?,
+            url,
             headers,
+            timeout: self.timeout,
+            retry: self.retry.clone(),
+            redirects: self.redirects,
+            oauth2,
+        })
+    }
+}
+#[async_trait]
+impl Fillable<RequestHeader> for FillableAuth{
+    async fn fill(&self, context: &Context) -> Result<RequestHeader> {
+        let value = match self {
+            FillableAuth::Basic(username,password)=>{
+                let username:String = username.fill(context).await?;
+                let password:String = password.fill(context).await?;
+                format!("Basic {}",base64::encode(format!("{}:{}",username,password)))
+            },
+            FillableAuth::Bearer(token)=>{
+                let token:String = token.fill(context).await?;
+                format!("Bearer {}",token)
+            },
+            FillableAuth::OAuth2(_)=>bail!("OAuth2 token is fetched when the request is sent")
+        };
+        Ok(RequestHeader{
+            key:"Authorization".to_string(),
+            value
+        })
+    }
+}
+#[async_trait]
+impl Fillable<OAuth2Request> for FillableOAuth2{
+    async fn fill(&self, context: &Context) -> Result<OAuth2Request> {
+        Ok(OAuth2Request{
+            url:self.url.fill(context).await?,
+            client_id:self.client_id.fill(context).await?,
+            client_secret:fill_optional(&self.client_secret,context).await?,
+            scope:fill_optional(&self.scope,context).await?,
+            username:fill_optional(&self.username,context).await?,
+            password:fill_optional(&self.password,context).await?,
+            cache:self.cache
         })
     }
 }
+async fn fill_optional(expr:&Option<Expression>,context:&Context)->Result<Option<String>>{
+    if let Some(expr) = expr {
+        Ok(Option::Some(expr.fill(context).await?))
+    } else {
+        Ok(Option::None)
+    }
+}
 #[async_trait]
 impl Fillable<String> for URL{
     async fn fill(&self, context: &Context) -> Result<String> {
@@ -130,10 +374,79 @@ impl Fillable<RequestBody> for FillableRequestBody{
         match self{
             FillableRequestBody::WithObject(obj)=>{
                 Ok(RequestBody::JSON(obj.fill(context).await?))
+            },
+            FillableRequestBody::WithForm(fields)=>{
+                let mut filled = vec![];
+                for field in fields {
+                    let value = match &field.value {
+                        FillableFormValue::WithExpression(expr)=>expr.fill(context).await?,
+                        FillableFormValue::WithText(text)=>text.fill(context).await?,
+                        FillableFormValue::File(_)=>bail!("Files can only be sent in a multipart body")
+                    };
+                    filled.push((field.name.clone(),value));
+                }
+                Ok(RequestBody::Form(filled))
+            },
+            FillableRequestBody::WithMultipart(fields)=>{
+                let mut filled = vec![];
+                for field in fields {
+                    let mut part = field.value.fill(context).await?;
+                    part.name = Option::Some(field.name.clone());
+                    filled.push(part);
+                }
+                Ok(RequestBody::Multipart(format!("corr-boundary-{:032x}",context.with_rng(|rng|rng.gen::<u128>())),filled))
+            },
+            FillableRequestBody::WithText(text)=>{
+                Ok(RequestBody::Text(text.fill(context).await?))
+            },
+            FillableRequestBody::WithBinary(expr)=>{
+                match expr.evaluate(context).await? {
+                    Value::Buffer(data)=>Ok(RequestBody::Binary(data)),
+                    Value::String(text)=>Ok(RequestBody::Binary(text.into_bytes())),
+                    _=>bail!("Binary body should be a buffer or a string")
+                }
             }
         }
     }
 }
+#[async_trait]
+impl Fillable<MultipartField> for FillableFormValue{
+    async fn fill(&self, context: &Context) -> Result<MultipartField> {
+        let field = match self {
+            FillableFormValue::WithExpression(expr)=>match expr.evaluate(context).await? {
+                Value::Buffer(data)=>MultipartField{
+                    name:Option::None,
+                    file_name:Option::None,
+                    content_type:Option::Some("application/octet-stream".to_string()),
+                    contents:Option::Some(Bytes::from(data))
+                },
+                value=>MultipartField{
+                    name:Option::None,
+                    file_name:Option::None,
+                    content_type:Option::None,
+                    contents:Option::Some(Bytes::from(value.to_string()))
+                }
+            },
+            FillableFormValue::WithText(text)=>MultipartField{
+                name:Option::None,
+                file_name:Option::None,
+                content_type:Option::None,
+                contents:Option::Some(Bytes::from(text.fill(context).await?))
+            },
+            FillableFormValue::File(path)=>{
+                let path:String = path.fill(context).await?;
+                let data = tokio::fs::read(&path).await.map_err(|e|anyhow!("Unable to read {}: {}",path,e))?;
+                MultipartField{
+                    name:Option::None,
+                    file_name:Path::new(&path).file_name().map(|name|name.to_string_lossy().to_string()),
+                    content_type:Option::Some(content_type_of(&path).to_string()),
+                    contents:Option::Some(Bytes::from(data))
+                }
+            }
+        };
+        Ok(field)
+    }
+}
 
 #[async_trait]
 impl Fillable<String> for FillableRequestHeaderValue{
@@ -181,6 +494,7 @@ mod tests{
     use crate::journey::step::rest::CorrRequest;
     use crate::core::{DataType, Value};
     use nom::lib::std::collections::HashMap;
+    use multer::bytes::Bytes;
 
     #[tokio::test]
     async fn should_fill_fillablerequest_when_only_url(){
@@ -196,7 +510,11 @@ mod tests{
             method:RestVerb::GET,
             url:format!("http://localhost/3"),
             body:Option::None,
-            headers:Option::None
+            headers:Option::None,
+            timeout:Option::None,
+            retry:Option::None,
+            redirects:Option::None,
+            oauth2:Option::None
         })
     }
     #[tokio::test]
@@ -219,7 +537,11 @@ mod tests{
             method:RestVerb::POST,
             url:format!("http://localhost/3"),
             body:Option::Some(RequestBody::JSON(Value::Map(mp))),
-            headers:Option::None
+            headers:Option::None,
+            timeout:Option::None,
+            retry:Option::None,
+            redirects:Option::None,
+            oauth2:Option::None
         })
     }
     #[tokio::test]
@@ -247,7 +569,11 @@ mod tests{
                     key:format!("X-API-KEY"),
                     value:format!("Something")
                 }]
-            })
+            }),
+            timeout:Option::None,
+            retry:Option::None,
+            redirects:Option::None,
+            oauth2:Option::None
         })
     }
     #[tokio::test]
@@ -279,7 +605,11 @@ mod tests{
                     key:format!("X-API-KEY"),
                     value:format!("Something")
                 }]
-            })
+            }),
+            timeout:Option::None,
+            retry:Option::None,
+            redirects:Option::None,
+            oauth2:Option::None
         })
     }
     #[tokio::test]
@@ -324,4 +654,63 @@ mod tests{
         let filled = frhv.fill(&context).await.unwrap();
         assert_eq!(filled,format!("ABC-Atmaram"))
     }
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn should_fill_form_and_text_bodies(){
+        let input=vec![Input::Continue(ContinueInput{name:"name".to_string(),value:"Atma Ram".to_string(),data_type:DataType::String})];
+        let buffer:Arc<Mutex<Vec<Output>>> = Arc::new(Mutex::new(vec![]));
+        let context=Context::mock(input,buffer.clone());
+        let (_,frb) = FillableRequestBody::parser(r#"form { "name": name, "city": "Pune&Co" }"#).unwrap();
+        let filled = frb.fill(&context).await.unwrap();
+        assert_eq!(filled.content_type(),"application/x-www-form-urlencoded");
+        assert_eq!(String::from_utf8(filled.to_bytes()).unwrap(),"name=Atma+Ram&city=Pune%26Co");
+        let (_,frb) = FillableRequestBody::parser(r#"text `Hello <%name%>`"#).unwrap();
+        let filled = frb.fill(&context).await.unwrap();
+        assert_eq!(filled,RequestBody::Text(format!("Hello Atma Ram")));
+        assert_eq!(filled.content_type(),"text/plain; charset=utf-8");
+    }
+    #[tokio::test]
+    async fn should_fill_multipart_body_readable_by_multer(){
+        let input=vec![Input::Continue(ContinueInput{name:"name".to_string(),value:"Atmaram".to_string(),data_type:DataType::String})];
+        let buffer:Arc<Mutex<Vec<Output>>> = Arc::new(Mutex::new(vec![]));
+        let context=Context::mock(input,buffer.clone());
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/journey/step/rest/testdata/ca.crt");
+        let txt = format!(r#"multipart {{ "file": file("{}"), "name": name }}"#,path);
+        let (_,frb) = FillableRequestBody::parser(txt.as_str()).unwrap();
+        let filled = frb.fill(&context).await.unwrap();
+        let boundary = multer::parse_boundary(filled.content_type()).unwrap();
+        let data = filled.to_bytes();
+        let mut multipart = multer::Multipart::new(futures::stream::once(async move { Ok::<_,std::io::Error>(Bytes::from(data)) }),boundary);
+        let file = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(file.name(),Option::Some("file"));
+        assert_eq!(file.file_name(),Option::Some("ca.crt"));
+        assert_eq!(file.bytes().await.unwrap().to_vec(),std::fs::read(path).unwrap());
+        let name = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(name.name(),Option::Some("name"));
+        assert_eq!(name.text().await.unwrap(),"Atmaram");
+        assert!(multipart.next_field().await.unwrap().is_none());
+    }
+    #[tokio::test]
+    async fn should_fill_binary_body_from_buffer(){
+        let buffer:Arc<Mutex<Vec<Output>>> = Arc::new(Mutex::new(vec![]));
+        let context=Context::mock(vec![],buffer.clone());
+        context.define(format!("data"),Value::Buffer(vec![0,159,146,150])).await;
+        let (_,frb) = FillableRequestBody::parser(r#"binary data"#).unwrap();
+        let filled = frb.fill(&context).await.unwrap();
+        assert_eq!(filled,RequestBody::Binary(vec![0,159,146,150]));
+        assert_eq!(filled.content_type(),"application/octet-stream");
+    }
+    #[tokio::test]
+    async fn should_fill_fillablerequest_with_encoded_query(){
+        let txt = r#"get request {
+            url: "http://localhost/search?lang=en",
+            query: { "q": term, "tag": tags, "page": 2 }
+        }"#;
+        let (_,fr) = FillableRequest::parser(txt).unwrap();
+        let buffer:Arc<Mutex<Vec<Output>>> = Arc::new(Mutex::new(vec![]));
+        let context=Context::mock(vec![],buffer.clone());
+        context.define(format!("term"),Value::String(format!("rust & nom ü"))).await;
+        context.define(format!("tags"),Value::Array(vec![Value::String(format!("a b")),Value::String(format!("c"))])).await;
+        let filled = fr.fill(&context).await.unwrap();
+        assert_eq!(filled.url,"http://localhost/search?lang=en&q=rust+%26+nom+%C3%BC&tag=a+b&tag=c&page=2");
+    }
+}
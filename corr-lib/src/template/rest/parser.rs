@@ -1,14 +1,19 @@
 use crate::parser::{Parsable, ParseResult, ws};
-use crate::template::rest::{RestVerb, FillableRequest, FillableRequestBody, FillableRequestHeaders, URL, FillableRequestHeaderPair, FillableRequestHeaderValue};
+use crate::template::rest::{RestVerb, FillableRequest, FillableRequestBody, FillableRequestHeaders, URL, FillableRequestHeaderPair, FillableRequestHeaderValue, RetryOn, RetryPolicy, FillableFormField, FillableFormValue, StatusPattern, FillableAuth, FillableOAuth2};
+use crate::journey::step::rest::auth::TokenScope;
+use crate::journey::step::rest::redirect::RedirectPolicy;
+use crate::core::Value;
 use nom::branch::alt;
-use nom::combinator::{map, opt};
+use nom::combinator::{map, map_opt, opt, value, verify};
+use hyper::Method;
 use nom::bytes::complete::tag;
-use nom::sequence::{preceded, tuple, terminated};
-use nom::character::complete::char;
+use nom::sequence::{delimited, preceded, tuple, terminated};
+use nom::character::complete::{char, one_of, u16, u64};
 use crate::template::text::Text;
 use crate::template::object::FillableObject;
-use nom::multi::separated_list1;
-use crate::core::parser::string;
+use nom::multi::{many0, separated_list0, separated_list1};
+use crate::core::parser::{boolean, duration_or, string};
+use std::time::Duration;
 use crate::template::Expression;
 
 impl Parsable for RestVerb {
@@ -19,10 +24,35 @@ impl Parsable for RestVerb {
             map(tag("post"),|_|RestVerb::POST),
             map(tag("patch"),|_|RestVerb::PATCH),
             map(tag("delete"),|_|RestVerb::DELETE),
+            map(tag("head"),|_|RestVerb::HEAD),
+            map(tag("options"),|_|RestVerb::OPTIONS),
+            map(tag("trace"),|_|RestVerb::TRACE),
+            map(tag("connect"),|_|RestVerb::CONNECT),
+            map(preceded(ws(tag("method")),verify(ws(string),|method:&str|Method::from_bytes(method.as_bytes()).is_ok())),RestVerb::Custom),
             ))(input)
     }
 }
 
+enum RequestOption{
+    Body(FillableRequestBody),
+    Headers(FillableRequestHeaders),
+    Query(Vec<FillableFormField>),
+    Auth(FillableAuth),
+    Timeout(Duration),
+    Retry(RetryPolicy),
+    Redirects(RedirectPolicy)
+}
+fn request_option<'a>(input: &'a str) -> ParseResult<'a, RequestOption> {
+    alt((
+        map(preceded(tuple((ws(tag("body")),ws(char(':')))),FillableRequestBody::parser),RequestOption::Body),
+        map(preceded(tuple((ws(tag("headers")),ws(char(':')))),FillableRequestHeaders::parser),RequestOption::Headers),
+        map(preceded(tuple((ws(tag("query")),ws(char(':')))),form_fields(form_value)),RequestOption::Query),
+        map(preceded(tuple((ws(tag("auth")),ws(char(':')))),FillableAuth::parser),RequestOption::Auth),
+        map(preceded(tuple((ws(tag("timeout")),ws(char(':')))),ws(duration_or(Duration::from_millis))),RequestOption::Timeout),
+        map(preceded(tuple((ws(tag("retry")),ws(char(':')))),RetryPolicy::parser),RequestOption::Retry),
+        map(preceded(tuple((ws(tag("redirects")),ws(char(':')))),redirect_policy),RequestOption::Redirects),
+    ))(input)
+}
 impl Parsable for FillableRequest{
     fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
         map(
@@ -31,18 +61,139 @@ impl Parsable for FillableRequest{
                 ws(tag("request")),
                 preceded(ws(char('{')),terminated(
                     tuple((preceded(tuple((ws(tag("url")),ws(char(':')))),URL::parser),
-                        opt(preceded(tuple((ws(char(',')),ws(tag("body")),ws(char(':')))),FillableRequestBody::parser)),
-                       opt(preceded(tuple((ws(char(',')),ws(tag("headers")),ws(char(':')))),FillableRequestHeaders::parser)),
+                        many0(preceded(ws(char(',')),request_option))
                     ))
                          ,ws(char('}'))))
 
                 ))
-        ,|(verb,_,(url,body,headers))|FillableRequest{
-                verb,headers,body,url
+        ,|(verb,_,(url,options))|{
+                let mut request = FillableRequest{
+                    verb,url,body:Option::None,headers:Option::None,query:Option::None,auth:Option::None,timeout:Option::None,retry:Option::None,redirects:Option::None
+                };
+                for option in options {
+                    match option {
+                        RequestOption::Body(body)=>request.body = Option::Some(body),
+                        RequestOption::Headers(headers)=>request.headers = Option::Some(headers),
+                        RequestOption::Query(query)=>request.query = Option::Some(query),
+                        RequestOption::Auth(auth)=>request.auth = Option::Some(auth),
+                        RequestOption::Timeout(timeout)=>request.timeout = Option::Some(timeout),
+                        RequestOption::Retry(retry)=>request.retry = Option::Some(retry),
+                        RequestOption::Redirects(redirects)=>request.redirects = Option::Some(redirects),
+                    }
+                }
+                request
+            }
+        )(input)
+    }
+}
+enum OAuth2Option{
+    Url(URL),
+    ClientId(Expression),
+    ClientSecret(Expression),
+    Scope(Expression),
+    Username(Expression),
+    Password(Expression),
+    Cache(TokenScope)
+}
+fn oauth2_option<'a>(input: &'a str) -> ParseResult<'a, OAuth2Option> {
+    alt((
+        map(preceded(tuple((ws(tag("url")),ws(char(':')))),ws(URL::parser)),OAuth2Option::Url),
+        map(preceded(tuple((ws(tag("clientId")),ws(char(':')))),ws(Expression::parser)),OAuth2Option::ClientId),
+        map(preceded(tuple((ws(tag("clientSecret")),ws(char(':')))),ws(Expression::parser)),OAuth2Option::ClientSecret),
+        map(preceded(tuple((ws(tag("scope")),ws(char(':')))),ws(Expression::parser)),OAuth2Option::Scope),
+        map(preceded(tuple((ws(tag("username")),ws(char(':')))),ws(Expression::parser)),OAuth2Option::Username),
+        map(preceded(tuple((ws(tag("password")),ws(char(':')))),ws(Expression::parser)),OAuth2Option::Password),
+        map(preceded(tuple((ws(tag("cache")),ws(char(':')))),alt((
+            value(TokenScope::Vu,ws(tag("vu"))),
+            value(TokenScope::Global,ws(tag("global")))
+        ))),OAuth2Option::Cache),
+    ))(input)
+}
+impl Parsable for FillableOAuth2{
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        map_opt(
+            preceded(ws(char('{')),terminated(separated_list1(ws(char(',')),oauth2_option),ws(char('}')))),
+            |options|{
+                let (mut url,mut client_id) = (Option::None,Option::None);
+                let mut oauth = FillableOAuth2{
+                    url:URL::WithExpression(Expression::Constant(Value::Null)),
+                    client_id:Expression::Constant(Value::Null),
+                    client_secret:Option::None,
+                    scope:Option::None,
+                    username:Option::None,
+                    password:Option::None,
+                    cache:TokenScope::Vu
+                };
+                for option in options {
+                    match option {
+                        OAuth2Option::Url(val)=>url = Option::Some(val),
+                        OAuth2Option::ClientId(val)=>client_id = Option::Some(val),
+                        OAuth2Option::ClientSecret(val)=>oauth.client_secret = Option::Some(val),
+                        OAuth2Option::Scope(val)=>oauth.scope = Option::Some(val),
+                        OAuth2Option::Username(val)=>oauth.username = Option::Some(val),
+                        OAuth2Option::Password(val)=>oauth.password = Option::Some(val),
+                        OAuth2Option::Cache(val)=>oauth.cache = val,
+                    }
+                }
+                //Token url and client id are required
+                oauth.url = url?;
+                oauth.client_id = client_id?;
+                Option::Some(oauth)
             }
         )(input)
     }
 }
+impl Parsable for FillableAuth{
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        alt((
+            map(preceded(ws(tag("basic")),preceded(ws(char('{')),terminated(tuple((
+                preceded(tuple((ws(tag("username")),ws(char(':')))),ws(Expression::parser)),
+                preceded(tuple((ws(char(',')),ws(tag("password")),ws(char(':')))),ws(Expression::parser)),
+            )),ws(char('}'))))),|(username,password)|FillableAuth::Basic(username,password)),
+            map(preceded(ws(tag("bearer")),ws(Expression::parser)),FillableAuth::Bearer),
+            map(preceded(ws(tag("oauth2")),map(FillableOAuth2::parser,Box::new)),FillableAuth::OAuth2),
+        ))(input)
+    }
+}
+impl Parsable for RetryOn{
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        alt((
+            map(ws(u16),RetryOn::Status),
+            value(RetryOn::Timeout,ws(tag("\"timeout\""))),
+            value(RetryOn::Error,ws(tag("\"error\""))),
+        ))(input)
+    }
+}
+impl Parsable for RetryPolicy{
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        map(delimited(ws(char('{')),tuple((
+            preceded(tuple((ws(tag("times")),ws(char(':')))),ws(u64)),
+            opt(preceded(tuple((ws(char(',')),ws(tag("backoff")),ws(char(':')))),ws(duration_or(Duration::from_millis)))),
+            opt(preceded(tuple((ws(char(',')),ws(tag("on")),ws(char(':')))),delimited(ws(char('[')),separated_list0(ws(char(',')),RetryOn::parser),ws(char(']'))))),
+        )),ws(char('}'))),|(times,backoff,on)|RetryPolicy{
+            times,
+            backoff:backoff.unwrap_or(Duration::from_millis(0)),
+            on:on.unwrap_or(vec![RetryOn::Timeout,RetryOn::Error])
+        })(input)
+    }
+}
+fn redirect_policy<'a>(input: &'a str) -> ParseResult<'a, RedirectPolicy> {
+    map(delimited(ws(char('{')),tuple((
+        preceded(tuple((ws(tag("max")),ws(char(':')))),ws(u64)),
+        opt(preceded(tuple((ws(char(',')),ws(tag("keepMethod")),ws(char(':')))),ws(boolean))),
+    )),ws(char('}'))),|(max,keep_method)|RedirectPolicy{
+        max:max as usize,
+        keep_method:keep_method.unwrap_or(false)
+    })(input)
+}
+impl Parsable for StatusPattern{
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        alt((
+            map(terminated(one_of("12345"),tag("xx")),|class|StatusPattern::Class(class.to_digit(10).unwrap_or(0) as u16)),
+            map(u16,StatusPattern::Exact)
+        ))(input)
+    }
+}
 impl Parsable for URL{
     fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
         alt((
@@ -54,10 +205,32 @@ impl Parsable for URL{
 }
 impl Parsable for FillableRequestBody{
     fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
-        map(FillableObject::parser,|obj|
-        FillableRequestBody::WithObject(obj))(input)
+        alt((
+            map(FillableObject::parser,|obj|FillableRequestBody::WithObject(obj)),
+            map(preceded(ws(tag("form")),form_fields(form_value)),FillableRequestBody::WithForm),
+            map(preceded(ws(tag("multipart")),form_fields(multipart_value)),FillableRequestBody::WithMultipart),
+            map(ws(Text::parser),FillableRequestBody::WithText),
+            map(preceded(ws(tag("binary")),ws(Expression::parser)),FillableRequestBody::WithBinary),
+        ))(input)
     }
 }
+fn form_value<'a>(input: &'a str) -> ParseResult<'a, FillableFormValue> {
+    alt((
+        map(ws(Expression::parser),FillableFormValue::WithExpression),
+        map(ws(Text::parser),FillableFormValue::WithText)
+    ))(input)
+}
+fn multipart_value<'a>(input: &'a str) -> ParseResult<'a, FillableFormValue> {
+    alt((
+        map(preceded(tuple((ws(tag("file")),ws(char('(')))),terminated(ws(Expression::parser),ws(char(')')))),FillableFormValue::File),
+        form_value
+    ))(input)
+}
+fn form_fields<'a>(value:fn(&'a str)->ParseResult<'a, FillableFormValue>) -> impl FnMut(&'a str) -> ParseResult<'a, Vec<FillableFormField>> {
+    preceded(ws(char('{')),terminated(
+        separated_list0(ws(char(',')),map(tuple((ws(string),ws(char(':')),value)),|(name,_,value)|FillableFormField{name,value})),
+        ws(char('}'))))
+}
 impl Parsable for FillableRequestHeaders{
     fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
         map(preceded(ws(char('{')),terminated(separated_list1(ws(char(',')),FillableRequestHeaderPair::parser),ws(char('}')))),|headers|
@@ -92,7 +265,10 @@ impl Parsable for FillableRequestHeaderValue{
 mod tests{
     use crate::parser::util::assert_if;
     use crate::parser::Parsable;
-    use crate::template::rest::{RestVerb, FillableRequest, URL, FillableRequestHeaders, FillableRequestHeaderPair, FillableRequestHeaderValue, FillableRequestBody};
+    use crate::template::rest::{RestVerb, FillableRequest, URL, FillableRequestHeaders, FillableRequestHeaderPair, FillableRequestHeaderValue, FillableRequestBody, RetryOn, RetryPolicy, FillableFormField, FillableFormValue, StatusPattern, FillableAuth, FillableOAuth2};
+    use crate::journey::step::rest::auth::TokenScope;
+    use crate::journey::step::rest::redirect::RedirectPolicy;
+    use std::time::Duration;
     use crate::template::Expression;
     use crate::core::Value;
     use crate::template::object::{FillableObject, FillableMapObject, FillablePair};
@@ -130,6 +306,20 @@ mod tests{
         assert_if(text,a,RestVerb::DELETE);
     }
     #[test]
+    fn should_parse_restverb_head_options_trace_and_connect(){
+        assert_if("head",RestVerb::parser("head"),RestVerb::HEAD);
+        assert_if("options",RestVerb::parser("options"),RestVerb::OPTIONS);
+        assert_if("trace",RestVerb::parser("trace"),RestVerb::TRACE);
+        assert_if("connect",RestVerb::parser("connect"),RestVerb::CONNECT);
+    }
+    #[test]
+    fn should_parse_restverb_custom_method(){
+        let text=r#"method "PROPFIND""#;
+        let a=RestVerb::parser(text);
+        assert_if(text,a,RestVerb::Custom(format!("PROPFIND")));
+        assert!(RestVerb::parser(r#"method "NOT A METHOD""#).is_err());
+    }
+    #[test]
     fn should_parse_fillablerequest_when_no_headers_and_body(){
         let text=r#"get request {
             url: "http://localhost"
@@ -139,7 +329,12 @@ mod tests{
             url:URL::WithExpression(Expression::Constant(Value::String(format!("http://localhost")))),
             verb:RestVerb::GET,
             body:Option::None,
-            headers:Option::None
+            headers:Option::None,
+            query:Option::None,
+            auth:Option::None,
+            timeout:Option::None,
+            retry:Option::None,
+            redirects:Option::None
         });
     }
 
@@ -161,7 +356,12 @@ mod tests{
                     key:format!("X-API-KEY"),
                     value:FillableRequestHeaderValue::WithExpression(Expression::Variable(format!("x_api_key"),Option::None))
                 }]
-            })
+            }),
+            query:Option::None,
+            auth:Option::None,
+            timeout:Option::None,
+            retry:Option::None,
+            redirects:Option::None
         });
     }
     #[test]
@@ -186,10 +386,100 @@ mod tests{
                     key:format!("X-API-KEY"),
                     value:FillableRequestHeaderValue::WithExpression(Expression::Variable(format!("x_api_key"),Option::None))
                 }]
-            })
+            }),
+            query:Option::None,
+            auth:Option::None,
+            timeout:Option::None,
+            retry:Option::None,
+            redirects:Option::None
         });
     }
 
+    #[test]
+    fn should_parse_fillablerequest_with_options_in_any_order(){
+        let text=r#"get request {
+            url: "http://localhost",
+            retry: { times: 3, backoff: 200ms, on: [502, 503, "timeout"] },
+            redirects: { max: 3, keepMethod: true },
+            headers: {
+                "X-API-KEY": x_api_key
+            },
+            timeout: 2s
+        }"#;
+        let a=FillableRequest::parser(text);
+        assert_if(text,a,FillableRequest{
+            url:URL::WithExpression(Expression::Constant(Value::String(format!("http://localhost")))),
+            verb:RestVerb::GET,
+            body:Option::None,
+            headers:Option::Some(FillableRequestHeaders{
+                headers:vec![FillableRequestHeaderPair{
+                    key:format!("X-API-KEY"),
+                    value:FillableRequestHeaderValue::WithExpression(Expression::Variable(format!("x_api_key"),Option::None))
+                }]
+            }),
+            query:Option::None,
+            auth:Option::None,
+            timeout:Option::Some(Duration::from_secs(2)),
+            retry:Option::Some(RetryPolicy{
+                times:3,
+                backoff:Duration::from_millis(200),
+                on:vec![RetryOn::Status(502),RetryOn::Status(503),RetryOn::Timeout]
+            }),
+            redirects:Option::Some(RedirectPolicy{max:3,keep_method:true})
+        });
+    }
+    #[test]
+    fn should_retry_on_timeouts_and_errors_with_constant_backoff_by_default(){
+        let text=r#"{ times: 2, backoff: 100 }"#;
+        let (_,policy)=RetryPolicy::parser(text).unwrap();
+        assert!(policy.should_retry(1,&RetryOn::Error));
+        assert!(!policy.should_retry(2,&RetryOn::Timeout));
+        assert!(!policy.should_retry(0,&RetryOn::Status(503)));
+        assert_eq!(policy.backoff_for(2),Duration::from_millis(100));
+    }
+    #[test]
+    fn should_parse_form_multipart_text_and_binary_bodies(){
+        let text=r#"form { "name": name, "city": text `Pune` }"#;
+        assert_if(text,FillableRequestBody::parser(text),FillableRequestBody::WithForm(vec![
+            FillableFormField{name:format!("name"),value:FillableFormValue::WithExpression(Expression::Variable(format!("name"),Option::None))},
+            FillableFormField{name:format!("city"),value:FillableFormValue::WithText(Text{blocks:vec![Block::Text(format!("Pune"))]})},
+        ]));
+        let text=r#"multipart { "file": file("a.png"), "name": name }"#;
+        assert_if(text,FillableRequestBody::parser(text),FillableRequestBody::WithMultipart(vec![
+            FillableFormField{name:format!("file"),value:FillableFormValue::File(Expression::Constant(Value::String(format!("a.png"))))},
+            FillableFormField{name:format!("name"),value:FillableFormValue::WithExpression(Expression::Variable(format!("name"),Option::None))},
+        ]));
+        let text=r#"text `Hello`"#;
+        assert_if(text,FillableRequestBody::parser(text),FillableRequestBody::WithText(Text{blocks:vec![Block::Text(format!("Hello"))]}));
+        let text=r#"binary data"#;
+        assert_if(text,FillableRequestBody::parser(text),FillableRequestBody::WithBinary(Expression::Variable(format!("data"),Option::None)));
+    }
+    #[test]
+    fn should_parse_status_patterns(){
+        assert_if("2xx",StatusPattern::parser("2xx"),StatusPattern::Class(2));
+        assert_if("404",StatusPattern::parser("404"),StatusPattern::Exact(404));
+        assert!(StatusPattern::is_expected(&[StatusPattern::Class(2),StatusPattern::Exact(404)],404));
+        assert!(!StatusPattern::is_expected(&[StatusPattern::Class(2)],302));
+        assert!(StatusPattern::is_expected(&[],302));
+    }
+    #[test]
+    fn should_parse_auth_options(){
+        let text=r#"basic { username: user, password: "pass" }"#;
+        assert_if(text,FillableAuth::parser(text),FillableAuth::Basic(Expression::Variable(format!("user"),Option::None),Expression::Constant(Value::String(format!("pass")))));
+        let text=r#"bearer token"#;
+        assert_if(text,FillableAuth::parser(text),FillableAuth::Bearer(Expression::Variable(format!("token"),Option::None)));
+        let text=r#"oauth2 { url: "http://localhost/token", clientId: "corr", username: user, password: pass, cache: global }"#;
+        assert_if(text,FillableAuth::parser(text),FillableAuth::OAuth2(Box::new(FillableOAuth2{
+            url:URL::WithExpression(Expression::Constant(Value::String(format!("http://localhost/token")))),
+            client_id:Expression::Constant(Value::String(format!("corr"))),
+            client_secret:Option::None,
+            scope:Option::None,
+            username:Option::Some(Expression::Variable(format!("user"),Option::None)),
+            password:Option::Some(Expression::Variable(format!("pass"),Option::None)),
+            cache:TokenScope::Global
+        })));
+        assert!(FillableAuth::parser(r#"oauth2 { clientId: "corr" }"#).is_err());
+    }
     #[test]
     fn should_parse_url_when_expression(){
         let text=r#"name"#;
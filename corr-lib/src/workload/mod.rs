@@ -1,4 +1,16 @@
 pub mod parser;
+use rand::Rng;
+use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::core::ThinkTime;
+use crate::core::feeder::FeederDeclaration;
+use crate::core::Variable;
+use crate::core::runtime::Context;
+use crate::journey::Journey;
+use crate::template::Expression;
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Result};
 #[derive( Clone,PartialEq,Debug)]
 pub struct WorkLoad1{
     pub name:String,
@@ -11,8 +23,11 @@ pub struct WorkLoad1{
 #[derive( Clone,PartialEq,Debug)]
 pub struct WorkLoad {
     pub name:String,
+    pub params:Vec<Variable>,
     pub scenarios: Vec<Scenario>,
     pub setup:Option<String>,
+    pub teardown:Option<String>,
+    pub feeders:Vec<FeederDeclaration>,
 }
 #[derive( Clone,PartialEq,Debug)]
 pub enum Scenario{
@@ -21,13 +36,249 @@ pub enum Scenario{
 }
 #[derive( Clone,PartialEq,Debug)]
 pub struct ModelScenario {
-    pub journey: String,
+    //Named after its journeys unless given a name
+    pub name:Option<String>,
+    pub journeys: Vec<WeightedJourney>,
     pub stages:Vec<ModelStage>,
-    pub force_stop:Option<u64>,
+    pub start_time:Option<Duration>,
+    pub graceful_stop:Option<Duration>,
+    pub force_stop:Option<Duration>,
+    pub think_time:Option<ThinkTime>,
+    pub pacing:Option<Duration>,
+    pub abort_on_error_rate:Option<f64>,
+    pub correct_latency:bool,
+    pub args:Vec<Expression>,
 }
 #[derive( Clone,PartialEq,Debug)]
 pub struct ModelStage {
     pub target:u64,
-    pub duration:u64,
+    pub duration:Duration,
 }
+#[derive( Clone,PartialEq,Debug)]
+pub struct WeightedJourney {
+    pub journey:String,
+    pub weight:u64,
+}
+impl WorkLoad {
+    //Share of the workload run by one of total agents, stage targets are split across agents
+    pub fn share(&self,index:usize,total:usize)->WorkLoad{
+        let mut workload = self.clone();
+        for scenario in workload.scenarios.iter_mut() {
+            match scenario {
+                Scenario::Closed(ms) | Scenario::Open(ms)=>{
+                    for stage in ms.stages.iter_mut() {
+                        stage.target = share_of(stage.target,index,total);
+                    }
+                }
+            }
+        }
+        //Setup and teardown run once, on the first agent, before and after all agents ran their share
+        if index != 0 {
+            workload.setup = Option::None;
+            workload.teardown = Option::None;
+        }
+        workload
+    }
+}
+fn share_of(value:u64,index:usize,total:usize)->u64{
+    let total = total.max(1) as u64;
+    value / total + if (index as u64) < value % total { 1 } else { 0 }
+}
+impl ModelScenario {
+    //A scenario over the journeys and stages with every option left at its default
+    pub fn new(journeys:Vec<WeightedJourney>,stages:Vec<ModelStage>)->Self{
+        ModelScenario{
+            name:Option::None,
+            journeys,
+            stages,
+            start_time:Option::None,
+            graceful_stop:Option::None,
+            force_stop:Option::None,
+            think_time:Option::None,
+            pacing:Option::None,
+            abort_on_error_rate:Option::None,
+            correct_latency:false,
+            args:vec![]
+        }
+    }
+    pub fn name(&self)->String{
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        self.journeys.iter().map(|wj|wj.journey.clone()).collect::<Vec<String>>().join(",")
+    }
+    pub fn pick_journey(&self,context:&Context)->String{
+        let total:u64 = self.journeys.iter().map(|wj|wj.weight).sum();
+        if total == 0 {
+            return self.journeys[0].journey.clone();
+        }
+        let mut point = context.with_rng(|rng|rng.gen_range(0..total));
+        for wj in &self.journeys {
+            if point < wj.weight {
+                return wj.journey.clone();
+            }
+            point -= wj.weight;
+        }
+        self.journeys[self.journeys.len()-1].journey.clone()
+    }
+    //Every journey the scenario picks should exist and, when args are passed, take exactly as many parameters
+    pub fn check_args(&self,journeys:&HashMap<String,Arc<Journey>>)->Result<()>{
+        for wj in &self.journeys {
+            let journey = journeys.get(&wj.journey).ok_or_else(||anyhow!("Journey {} of scenario {} not found",wj.journey,self.name()))?;
+            if !self.args.is_empty() && journey.params.len() != self.args.len() {
+                bail!("Journey {} takes {} parameters but scenario {} passes {} args",wj.journey,journey.params.len(),self.name(),self.args.len())
+            }
+        }
+        Ok(())
+    }
+    pub async fn bind_args(&self,journey:&Journey,context:&Context)->Result<()>{
+        for (param,arg) in journey.params.iter().zip(self.args.iter()) {
+            context.define(param.name.clone(),arg.evaluate(context).await?).await;
+        }
+        Ok(())
+    }
+}
+//Minimum iterations before the error rate of a scenario is trusted
+const MIN_ITERATIONS_FOR_ERROR_RATE:u64 = 10;
+#[derive(Debug,Default)]
+pub struct ScenarioStats {
+    iterations:AtomicU64,
+    failed:AtomicU64,
+    duration:AtomicU64,
+    corrected:AtomicU64,
+    corrected_iterations:AtomicU64,
+}
+impl ScenarioStats {
+    pub fn record(&self,success:bool,duration:u128){
+        self.iterations.fetch_add(1,Ordering::SeqCst);
+        self.duration.fetch_add(duration as u64,Ordering::SeqCst);
+        if !success {
+            self.failed.fetch_add(1,Ordering::SeqCst);
+        }
+    }
+    //Duration measured from when the iteration was supposed to start rather than when it did
+    pub fn record_corrected(&self,duration:u128){
+        self.corrected_iterations.fetch_add(1,Ordering::SeqCst);
+        self.corrected.fetch_add(duration as u64,Ordering::SeqCst);
+    }
+    pub fn iterations(&self)->u64{
+        self.iterations.load(Ordering::SeqCst)
+    }
+    pub fn failed(&self)->u64{
+        self.failed.load(Ordering::SeqCst)
+    }
+    pub fn average_duration(&self)->f64{
+        let iterations = self.iterations();
+        if iterations == 0 {
+            0.0
+        } else {
+            self.duration.load(Ordering::SeqCst) as f64 / iterations as f64
+        }
+    }
+    pub fn average_corrected_duration(&self)->Option<f64>{
+        let iterations = self.corrected_iterations.load(Ordering::SeqCst);
+        if iterations == 0 {
+            Option::None
+        } else {
+            Option::Some(self.corrected.load(Ordering::SeqCst) as f64 / iterations as f64)
+        }
+    }
+    pub fn error_rate(&self)->f64{
+        let iterations = self.iterations();
+        if iterations == 0 {
+            0.0
+        } else {
+            self.failed() as f64 / iterations as f64
+        }
+    }
+    pub fn exceeds(&self,rate:f64)->bool{
+        self.iterations() >= MIN_ITERATIONS_FOR_ERROR_RATE && self.error_rate() > rate
+    }
+}
+#[cfg(test)]
+mod tests{
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use crate::core::runtime::Context;
+    use crate::journey::Journey;
+    use crate::parser::Parsable;
+    use crate::workload::{ModelScenario, ModelStage, Scenario, ScenarioStats, WeightedJourney, WorkLoad};
 
+    #[test]
+    fn should_check_args_against_every_weighted_journey(){
+        let mut journeys = HashMap::new();
+        for text in ["`Browse`(base_url){\n    print text `browse`\n}","`Search`(base_url,tier){\n    print text `search`\n}"] {
+            let (_,journey) = Journey::parser(text).unwrap();
+            journeys.insert(journey.name.clone(),Arc::new(journey));
+        }
+        let (_,scenario) = ModelScenario::parser(r#"journeys:[{"Browse",70},{"Search",30}], stages:[{1,1}], args:[base_url]"#).unwrap();
+        assert!(scenario.check_args(&journeys).is_err());
+        let (_,scenario) = ModelScenario::parser(r#"journeys:[{"Browse",70},{"Search",30}], stages:[{1,1}]"#).unwrap();
+        assert!(scenario.check_args(&journeys).is_ok());
+        let (_,scenario) = ModelScenario::parser(r#"journey:"Checkout", stages:[{1,1}]"#).unwrap();
+        assert!(scenario.check_args(&journeys).is_err());
+    }
+    #[test]
+    fn should_pick_journey_by_weight(){
+        let scenario = ModelScenario::new(vec![
+            WeightedJourney{journey:"Browse".to_string(),weight:1},
+            WeightedJourney{journey:"Never".to_string(),weight:0}
+        ],vec![ModelStage{target:1,duration:Duration::from_secs(1)}]);
+        let context = Context::mock(vec![],Arc::new(Mutex::new(vec![])));
+        for _ in 0..100 {
+            assert_eq!(scenario.pick_journey(&context),"Browse".to_string())
+        }
+        assert_eq!(scenario.name(),"Browse,Never".to_string());
+        let named = ModelScenario{name:Option::Some("Storefront".to_string()),..scenario};
+        assert_eq!(named.name(),"Storefront".to_string())
+    }
+    #[test]
+    fn should_exceed_error_rate_only_after_minimum_iterations(){
+        let stats = ScenarioStats::default();
+        for _ in 0..5 {
+            stats.record(false,10);
+        }
+        assert!(!stats.exceeds(0.2));
+        for _ in 0..5 {
+            stats.record(true,30);
+        }
+        assert_eq!(stats.error_rate(),0.5);
+        assert_eq!(stats.average_duration(),20.0);
+        assert!(stats.exceeds(0.2));
+        assert!(!stats.exceeds(0.5));
+    }
+    #[test]
+    fn should_average_corrected_duration_only_over_corrected_iterations(){
+        let stats = ScenarioStats::default();
+        stats.record(true,100);
+        assert_eq!(stats.average_corrected_duration(),Option::None);
+        stats.record(true,100);
+        stats.record_corrected(100);
+        stats.record_corrected(500);
+        assert_eq!(stats.average_duration(),100.0);
+        assert_eq!(stats.average_corrected_duration(),Option::Some(300.0));
+    }
+    #[test]
+    fn should_split_stage_targets_across_agents(){
+        let workload = WorkLoad{
+            name:"Load".to_string(),
+            params:vec![],
+            scenarios:vec![Scenario::Closed(ModelScenario::new(
+                vec![WeightedJourney{journey:"Browse".to_string(),weight:1}],
+                vec![ModelStage{target:10,duration:Duration::from_secs(1)},ModelStage{target:1,duration:Duration::from_secs(1)}]
+            ))],
+            setup:Option::Some("Setup".to_string()),
+            teardown:Option::Some("Cleanup".to_string()),
+            feeders:vec![]
+        };
+        let shares:Vec<WorkLoad> = (0..3).map(|i|workload.share(i,3)).collect();
+        let targets:Vec<Vec<u64>> = shares.iter().map(|w|match &w.scenarios[0] {
+            Scenario::Closed(ms) | Scenario::Open(ms)=>ms.stages.iter().map(|s|s.target).collect()
+        }).collect();
+        assert_eq!(targets,vec![vec![4,1],vec![3,0],vec![3,0]]);
+        assert_eq!(shares[0].teardown,Option::Some("Cleanup".to_string()));
+        assert_eq!(shares[1].teardown,Option::None);
+        assert_eq!((shares[0].setup.clone(),shares[1].setup.clone()),(Option::Some("Setup".to_string()),Option::None));
+    }
+}
@@ -2,13 +2,16 @@ use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::{char, u64};
 use nom::combinator::{map, opt};
-use nom::multi::{separated_list0, separated_list1};
-use nom::sequence::{delimited, tuple};
-use crate::core::parser::string;
-use crate::core::Variable;
+use nom::multi::{many0, separated_list0, separated_list1};
+use nom::sequence::{delimited, preceded, tuple};
+use crate::core::parser::{boolean, double, duration_or, string};
+use std::time::Duration;
+use crate::core::{ThinkTime, Variable};
+use crate::core::feeder::FeederDeclaration;
 use crate::journey::parser::{parse_executable_name};
+use crate::template::Expression;
 use crate::parser::{Parsable, ParseResult, ws};
-use crate::workload::{ModelScenario, ModelStage, Scenario, WorkLoad};
+use crate::workload::{ModelScenario, ModelStage, Scenario, WeightedJourney, WorkLoad};
 
 impl Parsable for WorkLoad {
     fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
@@ -16,9 +19,14 @@ impl Parsable for WorkLoad {
             parse_executable_name,
                       ws(tag("(")),separated_list0(ws(tag(",")),Variable::parser),ws(tag(")")),ws(char('{')),
                       opt(delimited(tuple((ws(tag("startup")),ws(tag(":")))),ws(string), ws(tag(",")))),
+                      opt(delimited(tuple((ws(tag("teardown")),ws(tag(":")))),ws(string), ws(tag(",")))),
+                      opt(delimited(tuple((ws(tag("feeders")),ws(tag(":")),ws(tag("[")))),separated_list1(ws(tag(",")),FeederDeclaration::parser), tuple((ws(tag("]")),ws(tag(",")))))),
                       ws(tag("scenarios")),ws(tag(":")),delimited(ws(tag("[")),separated_list1(ws(tag(",")),ws(Scenario::parser)),ws(tag("]"))))),
-            |(name,_,_,_,_,setup,_,_,scenarios)| WorkLoad {
+            |(name,_,params,_,_,setup,teardown,feeders,_,_,scenarios)| WorkLoad {
+                params,
                 setup,
+                teardown,
+                feeders:feeders.unwrap_or(vec![]),
                 name,
             scenarios
         })(input)
@@ -37,25 +45,78 @@ impl Parsable for ModelStage{
         map(delimited(ws(tag("{")),tuple((
             ws(u64),
             ws(tag(",")),
-            ws(u64)
+            ws(duration_or(Duration::from_secs))
             )),ws(tag("}"))),|(target,_,duration)|ModelStage{
             target,
             duration
         })(input)
     }
 }
+impl Parsable for WeightedJourney{
+    fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
+        map(delimited(ws(tag("{")),tuple((
+            ws(string),
+            ws(tag(",")),
+            ws(u64)
+            )),ws(tag("}"))),|(journey,_,weight)|WeightedJourney{
+            journey,
+            weight
+        })(input)
+    }
+}
+fn scenario_journeys<'a>(input: &'a str) -> ParseResult<'a, Vec<WeightedJourney>> {
+    alt((
+        preceded(tuple((ws(tag("journeys")),ws(tag(":")))),delimited(ws(tag("[")),separated_list1(ws(tag(",")),WeightedJourney::parser),ws(tag("]")))),
+        map(preceded(tuple((ws(tag("journey")),ws(tag(":")))),ws(string)),|journey|vec![WeightedJourney{journey,weight:1}])
+    ))(input)
+}
+enum ScenarioOption{
+    Name(String),
+    StartTime(Duration),
+    GracefulStop(Duration),
+    ForceStop(Duration),
+    ThinkTime(ThinkTime),
+    Pacing(Duration),
+    AbortOnErrorRate(f64),
+    CorrectLatency(bool),
+    Args(Vec<Expression>)
+}
+fn scenario_option<'a>(input: &'a str) -> ParseResult<'a, ScenarioOption> {
+    alt((
+        map(preceded(tuple((ws(tag("name")),ws(tag(":")))),ws(string)),ScenarioOption::Name),
+        map(preceded(tuple((ws(tag("startTime")),ws(tag(":")))),ws(duration_or(Duration::from_secs))),ScenarioOption::StartTime),
+        map(preceded(tuple((ws(tag("gracefulStop")),ws(tag(":")))),ws(duration_or(Duration::from_secs))),ScenarioOption::GracefulStop),
+        map(preceded(tuple((ws(tag("forceStop")),ws(tag(":")))),ws(duration_or(Duration::from_secs))),ScenarioOption::ForceStop),
+        map(preceded(tuple((ws(tag("thinkTime")),ws(tag(":")))),ThinkTime::parser),ScenarioOption::ThinkTime),
+        map(preceded(tuple((ws(tag("pacing")),ws(tag(":")))),ws(duration_or(Duration::from_millis))),ScenarioOption::Pacing),
+        map(preceded(tuple((ws(tag("abortOnErrorRate")),ws(tag(":")))),ws(double)),ScenarioOption::AbortOnErrorRate),
+        map(preceded(tuple((ws(tag("correctLatency")),ws(tag(":")))),ws(boolean)),ScenarioOption::CorrectLatency),
+        map(preceded(tuple((ws(tag("args")),ws(tag(":")))),delimited(ws(tag("[")),separated_list0(ws(tag(",")),Expression::parser),ws(tag("]")))),ScenarioOption::Args),
+    ))(input)
+}
 impl Parsable for ModelScenario{
     fn parser<'a>(input: &'a str) -> ParseResult<'a, Self> {
         map(tuple((
-                ws(tag("journey")),ws(tag(":")),ws(string),ws(tag(",")),
+                scenario_journeys,ws(tag(",")),
                 ws(tag("stages")),ws(tag(":")),
                 delimited(ws(tag("[")),separated_list1(ws(tag(",")),ws(ModelStage::parser)),ws(tag("]"))),
-                opt(tuple((ws(tag(",")),ws(tag("forceStop")),ws(tag(":")),ws(u64))))
-            )),|(_,_,journey,_,_,_,stages,fs)|
-        ModelScenario{
-            journey,
-            stages,
-            force_stop:fs.map(|(_,_,_,stop)|stop.clone())
+                many0(preceded(ws(tag(",")),scenario_option))
+            )),|(journeys,_,_,_,stages,options)|{
+            let mut scenario = ModelScenario::new(journeys,stages);
+            for option in options {
+                match option {
+                    ScenarioOption::Name(name)=>scenario.name = Option::Some(name),
+                    ScenarioOption::StartTime(st)=>scenario.start_time = Option::Some(st),
+                    ScenarioOption::GracefulStop(gs)=>scenario.graceful_stop = Option::Some(gs),
+                    ScenarioOption::ForceStop(fs)=>scenario.force_stop = Option::Some(fs),
+                    ScenarioOption::ThinkTime(tt)=>scenario.think_time = Option::Some(tt),
+                    ScenarioOption::Pacing(p)=>scenario.pacing = Option::Some(p),
+                    ScenarioOption::AbortOnErrorRate(rate)=>scenario.abort_on_error_rate = Option::Some(rate),
+                    ScenarioOption::CorrectLatency(correct)=>scenario.correct_latency = correct,
+                    ScenarioOption::Args(args)=>scenario.args = args,
+                }
+            }
+            scenario
         })(input)
     }
 }
@@ -70,4 +131,93 @@ fn closed_model_scenario_parser<'a>(input: &'a str) -> ParseResult<'a, Scenario>
         ws(tag("executor")),ws(tag(":")),ws(tag("\"closed\"")),ws(tag(",")),
         ModelScenario::parser
     )),ws(tag("}"))),|(_,_,_,_,ms)|{Scenario::Closed(ms)})(input)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests{
+    use crate::parser::Parsable;
+    use crate::parser::util::{assert_if};
+    use crate::workload::{ModelScenario, ModelStage, Scenario, WeightedJourney, WorkLoad};
+    use std::time::Duration;
+    use crate::core::{ThinkTime, Value, Variable};
+    use crate::core::feeder::{FeedStrategy, FeederDeclaration};
+    use crate::template::Expression;
+
+    fn hello(stages:Vec<ModelStage>)->ModelScenario{
+        ModelScenario::new(vec![WeightedJourney{journey:"Hello".to_string(),weight:1}],stages)
+    }
+    #[test]
+    fn should_parse_workload_with_setup_and_teardown(){
+        let j= r#"`Checkout Load`(base_url){
+            startup: "Setup",
+            teardown: "Cleanup",
+            feeders: [user from "users.csv" unique],
+            scenarios: [{executor:"closed", journey:"Hello", stages:[{10,5}]}]
+        }"#;
+        assert_if(j,WorkLoad::parser(j),WorkLoad{
+            name:"Checkout Load".to_string(),
+            params:vec![Variable::new("base_url")],
+            setup:Option::Some("Setup".to_string()),
+            teardown:Option::Some("Cleanup".to_string()),
+            feeders:vec![FeederDeclaration{variable:"user".to_string(),path:"users.csv".to_string(),strategy:FeedStrategy::Unique}],
+            scenarios:vec![Scenario::Closed(hello(vec![ModelStage{target:10,duration:Duration::from_secs(5)}]))]
+        })
+    }
+    #[test]
+    fn should_parse_scenario_with_start_time_and_graceful_stop(){
+        let j= r#"{executor:"open", journey:"Hello", stages:[{10,30s},{0,5}], gracefulStop: 30, startTime: 10s}"#;
+        assert_if(j,Scenario::parser(j),Scenario::Open(ModelScenario{
+            start_time:Option::Some(Duration::from_secs(10)),
+            graceful_stop:Option::Some(Duration::from_secs(30)),
+            ..hello(vec![ModelStage{target:10,duration:Duration::from_secs(30)},ModelStage{target:0,duration:Duration::from_secs(5)}])
+        }))
+    }
+    #[test]
+    fn should_parse_scenario_with_weighted_journeys(){
+        let j= r#"{executor:"closed", journeys:[{"Browse",70},{"Search",20},{"Checkout",10}], stages:[{10,5}], name:"Storefront"}"#;
+        assert_if(j,Scenario::parser(j),Scenario::Closed(ModelScenario{
+            name:Option::Some("Storefront".to_string()),
+            ..ModelScenario::new(vec![
+                WeightedJourney{journey:"Browse".to_string(),weight:70},
+                WeightedJourney{journey:"Search".to_string(),weight:20},
+                WeightedJourney{journey:"Checkout".to_string(),weight:10}
+            ],vec![ModelStage{target:10,duration:Duration::from_secs(5)}])
+        }))
+    }
+    #[test]
+    fn should_parse_scenario_with_think_time_and_pacing(){
+        let j= r#"{executor:"closed", journey:"Hello", stages:[{10,5}], thinkTime: uniform(500,1500), pacing: 5s}"#;
+        assert_if(j,Scenario::parser(j),Scenario::Closed(ModelScenario{
+            think_time:Option::Some(ThinkTime::Uniform(Duration::from_millis(500),Duration::from_millis(1500))),
+            pacing:Option::Some(Duration::from_secs(5)),
+            ..hello(vec![ModelStage{target:10,duration:Duration::from_secs(5)}])
+        }))
+    }
+    #[test]
+    fn should_parse_scenario_with_args(){
+        let j= r#"{executor:"open", journey:"Hello", stages:[{10,5}], args: [base_url, "premium"]}"#;
+        assert_if(j,Scenario::parser(j),Scenario::Open(ModelScenario{
+            args:vec![
+                Expression::Variable("base_url".to_string(),Option::None),
+                Expression::Constant(Value::String("premium".to_string()))
+            ],
+            ..hello(vec![ModelStage{target:10,duration:Duration::from_secs(5)}])
+        }))
+    }
+    #[test]
+    fn should_parse_scenario_with_abort_on_error_rate(){
+        let j= r#"{executor:"closed", journey:"Hello", stages:[{10,5}], abortOnErrorRate: 0.2}"#;
+        assert_if(j,Scenario::parser(j),Scenario::Closed(ModelScenario{
+            abort_on_error_rate:Option::Some(0.2),
+            ..hello(vec![ModelStage{target:10,duration:Duration::from_secs(5)}])
+        }))
+    }
+    #[test]
+    fn should_parse_scenario_with_latency_correction(){
+        let j= r#"{executor:"closed", journey:"Hello", stages:[{10,5}], pacing: 2s, correctLatency: true}"#;
+        assert_if(j,Scenario::parser(j),Scenario::Closed(ModelScenario{
+            pacing:Option::Some(Duration::from_secs(2)),
+            correct_latency:true,
+            ..hello(vec![ModelStage{target:10,duration:Duration::from_secs(5)}])
+        }))
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use corr_lib::core::runtime::IO;
 use corr_lib::core::runtime::Context as CorrContext;
+use corr_lib::core::clock::waiting;
 
 use corr_lib::core::proto::{Input, Output};
 
@@ -25,7 +26,7 @@ use corr_lib::parser::Parsable;
 
 use corr_lib::workload::WorkLoad;
 use anyhow::Result;
-pub async fn start_internal(journey:Arc<Journey>,context:CorrContext) {
+pub async fn start_internal(journey:Arc<Journey>,context:CorrContext)->bool {
     for param in journey.params.clone(){
         if let Err(_e) = context.read(param.clone()).await {
             panic!("Parameter {} not defined",param.name)
@@ -35,32 +36,41 @@ pub async fn start_internal(journey:Arc<Journey>,context:CorrContext) {
     match handles {
         Err(e)=>{
             eprintln!("Error {} while executing journey {}",e,journey.name);
+            false
         },
         Ok(handles)=>{
-            futures::future::join_all(handles).await;
+            let results = waiting(futures::future::join_all(handles)).await;
+            let failed = results.iter().any(|r|!matches!(r,Ok(Ok(_))));
+            !failed && !context.has_failed()
         }
     }
 
 }
-pub async fn start(journey:Arc<Journey>,mut context:CorrContext) {
+//Returns false when the journey errored, exited with a non zero code or any of its rest calls failed
+pub async fn start(journey:Arc<Journey>,mut context:CorrContext)->bool {
     let mut rx = context.exiter();
     let user = context.user.clone();
-    tokio::select! {
-        _ = rx.recv() => {},
-        _ = start_internal(journey,context) => {},
-    }
+    let checker = context.clone();
+    let success = tokio::select! {
+        code = rx.recv() => code.unwrap_or(0) == 0,
+        success = start_internal(journey,context) => success,
+    };
     user.lock().await.send(Output::new_done("Done Executing Journey".to_string())).await.unwrap();
+    success && !checker.has_failed()
 }
 pub fn unpack(target:String) -> Result<String, std::io::Error> {
+    unpack_into(target,"./target".to_string())
+}
+pub fn unpack_into(target:String,dir:String) -> Result<String, std::io::Error> {
     let tc = target.clone();
     let path = Path::new(tc.as_str());
     let name = path.file_stem();
     let tar_gz = File::open(target)?;
     let tar = GzDecoder::new(tar_gz);
     let mut archive = Archive::new(tar);
-    let _ = remove_dir_all("./target");
-    create_dir_all("./target")?;
-    let jp = format!("./target/{}",name.unwrap().to_str().unwrap());
+    let _ = remove_dir_all(dir.as_str());
+    create_dir_all(dir.as_str())?;
+    let jp = format!("{}/{}",dir,name.unwrap().to_str().unwrap());
     archive.unpack(jp.clone())?;
     Ok(jp)
 }
@@ -8,8 +8,9 @@ use std::path::Path;
 use std::sync::Arc;
 use corr_lib::journey::{Executable, Journey};
 use crate::client::{get_journeis_in, get_workloads_in};
-use crate::Out;
+use crate::{Out, RunOptions};
 use crate::runners::workload::WorkLoadRunner;
+use crate::runners::controller::ControllerRunner;
 use async_recursion::async_recursion;
 use corr_lib::workload::Scenario;
 
@@ -44,12 +45,14 @@ async fn pack(target:String,root:String,is_workload:bool) -> Result<String, std:
     tar.append_dir_all("./src", format!("{}/build/src",target))?;
     Ok(result)
 }
-pub async fn run(target:String, item:String, is_journey:bool, out:Out,debug:bool){
+pub async fn run(target:String, item:String, is_journey:bool, out:Out,options:RunOptions){
     if is_journey
     {
-        JourneyRunner::run(target, item,out,debug).await;
+        JourneyRunner::run(target, item,out,options).await;
+    } else if !options.agents.is_empty() {
+        ControllerRunner::run(target,item,out,options).await;
     } else {
-        WorkLoadRunner::run(target,item,out,debug).await;
+        WorkLoadRunner::run(target,item,out,options).await;
     }
 }
 pub async fn copy_dependencies_in(target_dir:String, source:String, item:String, is_workload:bool){
@@ -79,13 +82,15 @@ async fn settle_workload(target_dir: String, source: String, item: String, jrns_
         if let Some(s) = &wl.setup {
             settle_journey(target_dir.clone(), source.clone(), s.clone(), jrns_arc.clone()).await;
         }
+        if let Some(t) = &wl.teardown {
+            settle_journey(target_dir.clone(), source.clone(), t.clone(), jrns_arc.clone()).await;
+        }
         for sc in &wl.scenarios {
             match sc {
-                Scenario::Open(ms)=>{
-                    settle_journey(target_dir.clone(), source.clone(), ms.journey.clone(), jrns_arc.clone()).await;
-                },
-                Scenario::Closed(ms)=>{
-                    settle_journey(target_dir.clone(), source.clone(), ms.journey.clone(), jrns_arc.clone()).await;
+                Scenario::Open(ms) | Scenario::Closed(ms)=>{
+                    for wj in &ms.journeys {
+                        settle_journey(target_dir.clone(), source.clone(), wj.journey.clone(), jrns_arc.clone()).await;
+                    }
                 }
             }
         }
@@ -1,7 +1,20 @@
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use crate::launcher::{build, run};
+use crate::runners::agent::AgentRunner;
 use clap::{Parser, Subcommand};
 use simple_error::SimpleError;
+use corr_lib::core::clock::VirtualClock;
+use corr_lib::core::runtime::{Client, Context as CorrContext};
+use corr_lib::core::scrapper::Scrapper;
+use corr_lib::journey::Journey;
+use futures::lock::Mutex;
+use corr_lib::journey::step::rest::client::{ConnectionModel, HttpClients, HttpConfig};
+use corr_lib::journey::step::rest::proxy::{ProxyConfig, ResolveOverride};
+use corr_lib::journey::step::rest::redirect::RedirectPolicy;
+use corr_lib::journey::step::rest::tls::{TlsConfig, TlsVersion};
 
 pub mod client;
 pub mod launcher;
@@ -27,14 +40,67 @@ async fn main() {
             target,
             item,
             workload,
+            params,
+            seed,
+            virtual_time,
+            agents,
+            agent_token,
+            control,
+            summary,
+            connections,
+            pool_size,
+            idle_timeout,
+            request_timeout,
+            ca,
+            client_cert,
+            client_key,
+            insecure,
+            tls_min_version,
+            max_redirects,
+            keep_method_on_redirect,
+            proxy,
+            resolve,
         } => {
+            let options = RunOptions {
+                debug,
+                params,
+                seed,
+                virtual_time,
+                agents,
+                agent_token,
+                control,
+                summary,
+                http: HttpConfig {
+                    model: connections,
+                    pool_size,
+                    idle_timeout: idle_timeout.map(Duration::from_secs),
+                    timeout: request_timeout.map(Duration::from_millis),
+                    tls: TlsConfig {
+                        ca,
+                        cert: client_cert,
+                        key: client_key,
+                        insecure,
+                        min_version: tls_min_version,
+                    },
+                    redirects: RedirectPolicy {
+                        max: max_redirects,
+                        keep_method: keep_method_on_redirect,
+                    },
+                    proxy: match proxy {
+                        Some(proxy) => ProxyConfig::from_env().with_proxy(proxy),
+                        None => ProxyConfig::from_env(),
+                    },
+                    resolve,
+                },
+            };
             if package {
-                run(target.clone(), item.clone(), !workload, out.clone(), debug).await
+                run(target.clone(), item.clone(), !workload, out.clone(), options).await
             } else {
                 let target = build(target.clone(), item.clone(), workload.clone()).await.unwrap();
-                run(target, item.clone(), !workload, out.clone(), debug).await
+                run(target, item.clone(), !workload, out.clone(), options).await
             }
         }
+        SubCommands::Agent { listen, token } => AgentRunner::listen(listen, token).await,
     };
 }
 
@@ -46,6 +112,7 @@ struct Opts {
 }
 
 #[derive(Subcommand,Debug)]
+#[allow(clippy::large_enum_variant)]
 enum SubCommands {
     #[clap(alias = "run")]
     Run {
@@ -64,6 +131,66 @@ enum SubCommands {
         #[arg(short, long)]
         debug: bool,
 
+        #[arg(long = "param", value_name = "NAME=VALUE")]
+        params: Vec<Param>,
+
+        #[arg(long)]
+        seed: Option<u64>,
+
+        #[arg(long)]
+        virtual_time: bool,
+
+        #[arg(long, value_delimiter = ',', value_name = "HOST:PORT")]
+        agents: Vec<String>,
+
+        #[arg(long, env = "CORR_AGENT_TOKEN", hide_env_values = true, value_name = "TOKEN")]
+        agent_token: Option<String>,
+
+        #[arg(long, value_name = "HOST:PORT")]
+        control: Option<String>,
+
+        #[arg(long, value_name = "FILE")]
+        summary: Option<String>,
+
+        #[arg(long, default_value = "shared", value_name = "shared|per-vu|per-request")]
+        connections: ConnectionModel,
+
+        #[arg(long)]
+        pool_size: Option<usize>,
+
+        #[arg(long, value_name = "SECS")]
+        idle_timeout: Option<u64>,
+
+        #[arg(long, value_name = "MILLIS")]
+        request_timeout: Option<u64>,
+
+        #[arg(long, value_name = "FILE")]
+        ca: Option<String>,
+
+        #[arg(long, value_name = "FILE", requires = "client_key")]
+        client_cert: Option<String>,
+
+        #[arg(long, value_name = "FILE", requires = "client_cert")]
+        client_key: Option<String>,
+
+        #[arg(long)]
+        insecure: bool,
+
+        #[arg(long, value_name = "1.2|1.3")]
+        tls_min_version: Option<TlsVersion>,
+
+        #[arg(long, default_value = "10", value_name = "HOPS")]
+        max_redirects: usize,
+
+        #[arg(long)]
+        keep_method_on_redirect: bool,
+
+        #[arg(long, value_name = "URL")]
+        proxy: Option<String>,
+
+        #[arg(long, value_name = "HOST:PORT:IP")]
+        resolve: Vec<ResolveOverride>,
+
         #[arg(default_value = "<default>")]
         item: String,
 
@@ -79,6 +206,13 @@ enum SubCommands {
         #[arg(default_value = "<default>")]
         item: String,
     },
+    Agent {
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: String,
+
+        #[arg(long, env = "CORR_AGENT_TOKEN", hide_env_values = true)]
+        token: String,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -98,6 +232,53 @@ impl FromStr for Out {
         }
     }
 }
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for Param {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => Ok(Param {
+                name: name.trim().to_string(),
+                value: value.to_string(),
+            }),
+            _ => Err(SimpleError::new("Expected parameter as NAME=VALUE")),
+        }
+    }
+}
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub debug: bool,
+    pub params: Vec<Param>,
+    pub seed: Option<u64>,
+    pub virtual_time: bool,
+    pub agents: Vec<String>,
+    pub agent_token: Option<String>,
+    pub control: Option<String>,
+    pub summary: Option<String>,
+    pub http: HttpConfig,
+}
+
+impl RunOptions {
+    //Context for a run, with its own http clients and the seed and clock asked for
+    pub fn context(&self, user: Arc<Mutex<dyn Client>>, journeys: HashMap<String, Arc<Journey>>, scrapper: Arc<Box<dyn Scrapper>>) -> anyhow::Result<CorrContext> {
+        let http = HttpClients::new(self.http.clone())?;
+        let mut context = CorrContext::new(user, journeys, scrapper, http, self.debug);
+        if let Some(seed) = self.seed {
+            context.set_seed(seed);
+        }
+        if self.virtual_time {
+            context.set_clock(Arc::new(VirtualClock::new()));
+        }
+        println!("Using seed {}", context.seed);
+        Ok(context)
+    }
+}
 // #[async_trait]
 // pub trait Executable{
 //     async fn execute(&self);
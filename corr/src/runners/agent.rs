@@ -0,0 +1,222 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::sync::Notify;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::sleep;
+use corr_lib::core::clock::Clock;
+use corr_lib::core::scrapper::{Metrics, Scrapper};
+use corr_lib::journey::step::rest::client::HttpConfig;
+use anyhow::{bail, Result};
+use crate::{client, Param, RunOptions};
+use crate::runners::workload::{find_workload, prepare_workload, run_scenarios, setup_workload, teardown_workload};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum AgentMessage{
+    Prepare{
+        token:String,
+        workload:String,
+        #[serde(with = "base64_bytes")]
+        package:Vec<u8>,
+        index:usize,
+        total:usize,
+        params:Vec<(String,String)>,
+        seed:Option<u64>,
+        http:Box<HttpConfig>,
+    },
+    Ready,
+    Setup,
+    SetupDone,
+    Start{
+        at:u64,
+    },
+    Metric{
+        series:String,
+        data:f64,
+        tags:Vec<(String,String)>,
+    },
+    Stop,
+    Done,
+    Teardown,
+    TeardownDone,
+    Error{
+        message:String,
+    },
+}
+//Package travels as base64 text rather than a json array of numbers
+mod base64_bytes{
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S:Serializer>(bytes:&[u8],serializer:S)->Result<S::Ok,S::Error>{
+        serializer.serialize_str(base64::encode(bytes).as_str())
+    }
+    pub fn deserialize<'de,D:Deserializer<'de>>(deserializer:D)->Result<Vec<u8>,D::Error>{
+        let encoded = String::deserialize(deserializer)?;
+        base64::decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+pub async fn send(writer:&mut OwnedWriteHalf,message:&AgentMessage)->Result<()>{
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+//Compares every byte so the time taken doesn't tell how much of the token was right
+fn same_token(given:&str,expected:&str)->bool{
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0,|diff,(g,e)|diff | (g ^ e)) == 0
+}
+pub fn epoch_millis()->u64{
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+//Forwards every ingested metric to the controller
+pub struct RemoteScrapper{
+    sender:UnboundedSender<AgentMessage>,
+}
+#[async_trait]
+impl Scrapper for RemoteScrapper{
+    async fn start_metrics_loop(&self,clock:Arc<dyn Clock>){
+        loop {
+            clock.sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn ingest(&self,series:&str,data:f64,tags:Vec<(String,String)>){
+        let _ = self.sender.send(AgentMessage::Metric{
+            series:series.to_string(),
+            data,
+            tags,
+        });
+    }
+
+    async fn ingest_metric(&self,_metrics:Arc<Metrics>,_tag:(String,String)) {}
+}
+pub struct AgentRunner;
+impl AgentRunner{
+    pub async fn listen(address:String,token:String){
+        let listener = TcpListener::bind(address.as_str()).await.unwrap();
+        println!("Agent listening on {}",address);
+        Self::accept(listener,token).await
+    }
+    pub async fn accept(listener:TcpListener,token:String){
+        let port = listener.local_addr().unwrap().port();
+        loop {
+            match listener.accept().await {
+                Ok((stream,peer))=>{
+                    println!("Controller {} connected",peer);
+                    if let Err(e) = Self::serve(stream,port,token.as_str()).await {
+                        eprintln!("Agent run failed: {}",e);
+                    }
+                    println!("Controller {} disconnected",peer);
+                }
+                Err(e)=>eprintln!("Unable to accept controller connection: {}",e),
+            }
+        }
+    }
+    async fn serve(stream:TcpStream,port:u16,token:&str)->Result<()>{
+        let (reader,mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let (workload,package,index,total,params,seed,http) = match Self::next(&mut lines).await? {
+            AgentMessage::Prepare{token:given,..} if !same_token(given.as_str(),token)=>{
+                send(&mut writer,&AgentMessage::Error{message:"Invalid agent token".to_string()}).await?;
+                bail!("Controller sent an invalid agent token")
+            }
+            AgentMessage::Prepare{workload,package,index,total,params,seed,http,..}=>(workload,package,index,total,params,seed,http),
+            other=>bail!("Expected prepare from controller but got {:?}",other),
+        };
+        let dir = format!("./target/agent-{}",port);
+        let jpack = format!("{}.jpack",dir);
+        tokio::fs::create_dir_all("./target").await?;
+        tokio::fs::write(jpack.as_str(),package).await?;
+        let jp = client::unpack_into(jpack,dir)?;
+        let (wl,jrns) = if let Some(found) = find_workload(jp,workload.clone()).await {
+            found
+        } else {
+            send(&mut writer,&AgentMessage::Error{message:format!("Workload {} not found",workload)}).await?;
+            bail!("Workload {} not found",workload)
+        };
+        let wl = wl.share(index,total);
+        let options = RunOptions{
+            params:params.into_iter().map(|(name,value)| Param{name,value}).collect(),
+            seed:seed.map(|s|s.wrapping_add(index as u64)),
+            http:*http,
+            ..RunOptions::default()
+        };
+        let (tx,mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let scrapper:Arc<Box<dyn Scrapper>> = Arc::new(Box::new(RemoteScrapper{sender:tx.clone()}));
+        let context = if let Some(context) = prepare_workload(&wl,jrns.clone(),scrapper.clone(),&options).await {
+            context
+        } else {
+            send(&mut writer,&AgentMessage::Error{message:format!("Unable to prepare workload {}",workload)}).await?;
+            bail!("Unable to prepare workload {}",workload)
+        };
+        send(&mut writer,&AgentMessage::Ready).await?;
+        //Controller asks only the first agent to set up, before any agent starts
+        let at = loop {
+            match Self::next(&mut lines).await? {
+                AgentMessage::Setup=>{
+                    let reply = if setup_workload(&wl,&jrns,&context).await {
+                        AgentMessage::SetupDone
+                    } else {
+                        AgentMessage::Error{message:format!("Setup of workload {} failed",workload)}
+                    };
+                    send(&mut writer,&reply).await?;
+                }
+                AgentMessage::Start{at}=>break at,
+                other=>bail!("Expected setup or start from controller but got {:?}",other),
+            }
+        };
+        let now = epoch_millis();
+        if at > now {
+            sleep(Duration::from_millis(at - now)).await;
+        }
+        let stop = Arc::new(Notify::new());
+        let stopper = stop.clone();
+        let (teardown,mut torn_down) = tokio::sync::mpsc::unbounded_channel();
+        //A controller that went away stops the run same as an explicit stop
+        tokio::spawn(async move {
+            while let Ok(Some(line)) = lines.next_line().await {
+                match serde_json::from_str(line.as_str()) {
+                    Ok(AgentMessage::Stop)=>stopper.notify_one(),
+                    Ok(AgentMessage::Teardown)=>{
+                        let _ = teardown.send(());
+                    }
+                    _=>{}
+                }
+            }
+            stopper.notify_one();
+        });
+        let forwarder = tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let done = matches!(message,AgentMessage::Done);
+                if send(&mut writer,&message).await.is_err() || done {
+                    break;
+                }
+            }
+            writer
+        });
+        println!("Running share {} of {} for workload {}",index + 1,total,wl.name);
+        if !run_scenarios(&wl,&jrns,scrapper,&options,&context,stop).await {
+            let _ = tx.send(AgentMessage::Error{message:format!("Unable to run workload {}",workload)});
+        }
+        let _ = tx.send(AgentMessage::Done);
+        let mut writer = forwarder.await?;
+        //Teardown is asked for once every agent is done
+        if torn_down.recv().await.is_some() {
+            teardown_workload(&wl,&jrns,&context).await;
+            send(&mut writer,&AgentMessage::TeardownDone).await?;
+        }
+        Ok(())
+    }
+    async fn next(lines:&mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>)->Result<AgentMessage>{
+        if let Some(line) = lines.next_line().await? {
+            Ok(serde_json::from_str(line.as_str())?)
+        } else {
+            bail!("Controller closed the connection")
+        }
+    }
+}
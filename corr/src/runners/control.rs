@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use serde::Serialize;
+use corr_lib::core::clock::Clock;
+use corr_lib::workload::{ModelStage, ScenarioStats};
+use crate::runners::summary::{ScenarioSummary, StageSummary};
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ScenarioStatus{
+    pub scenario:String,
+    pub executor:String,
+    pub stage:usize,
+    pub stages:usize,
+    pub target:u64,
+    pub current:u64,
+    pub paused:bool,
+    pub state:String,
+}
+#[derive(Default)]
+struct Timeline{
+    started:Option<SystemTime>,
+    current:Option<(usize,SystemTime)>,
+    stages:Vec<(usize,Duration)>,
+    finished:Option<SystemTime>,
+}
+//Commands issued through the control api, read by the scenario schedulers on every tick.
+//Stage and run times are taken from the clock of the run, so they follow virtual time too
+pub struct ScenarioControl{
+    pub stats:ScenarioStats,
+    clock:Arc<dyn Clock>,
+    planned:Vec<ModelStage>,
+    timeline:Mutex<Timeline>,
+    reached:AtomicU64,
+    running:AtomicU64,
+    peak_vus:AtomicU64,
+    status:Mutex<ScenarioStatus>,
+    paused:AtomicBool,
+    next_stage:AtomicBool,
+    stopping:AtomicBool,
+    target:Mutex<Option<u64>>,
+}
+impl ScenarioControl{
+    pub fn new(scenario:String,executor:&str,stages:&[ModelStage],clock:Arc<dyn Clock>)->Self{
+        ScenarioControl{
+            stats:ScenarioStats::default(),
+            clock,
+            planned:stages.to_vec(),
+            timeline:Mutex::new(Timeline::default()),
+            reached:AtomicU64::new(0),
+            running:AtomicU64::new(0),
+            peak_vus:AtomicU64::new(0),
+            status:Mutex::new(ScenarioStatus{
+                scenario,
+                executor:executor.to_string(),
+                stages:stages.len(),
+                state:"waiting".to_string(),
+                ..ScenarioStatus::default()
+            }),
+            paused:AtomicBool::new(false),
+            next_stage:AtomicBool::new(false),
+            stopping:AtomicBool::new(false),
+            target:Mutex::new(Option::None),
+        }
+    }
+    pub fn name(&self)->String{
+        self.status.lock().unwrap().scenario.clone()
+    }
+    pub fn update(&self,change:impl FnOnce(&mut ScenarioStatus)){
+        change(&mut self.status.lock().unwrap())
+    }
+    pub fn status(&self)->ScenarioStatus{
+        let mut status = self.status.lock().unwrap().clone();
+        status.paused = self.is_paused();
+        status
+    }
+    pub fn pause(&self){
+        self.paused.store(true,Ordering::SeqCst)
+    }
+    pub fn resume(&self){
+        self.paused.store(false,Ordering::SeqCst)
+    }
+    pub fn is_paused(&self)->bool{
+        self.paused.load(Ordering::SeqCst)
+    }
+    pub fn skip_stage(&self){
+        self.next_stage.store(true,Ordering::SeqCst)
+    }
+    pub fn take_skip_stage(&self)->bool{
+        self.next_stage.swap(false,Ordering::SeqCst)
+    }
+    pub fn stop(&self){
+        self.stopping.store(true,Ordering::SeqCst);
+        self.resume();
+    }
+    pub fn is_stopping(&self)->bool{
+        self.stopping.load(Ordering::SeqCst)
+    }
+    //Overrides the target of the current stage until the next one begins
+    pub fn scale(&self,target:u64){
+        *self.target.lock().unwrap() = Option::Some(target)
+    }
+    pub fn target_override(&self)->Option<u64>{
+        *self.target.lock().unwrap()
+    }
+    //VUs of closed scenarios or iterations per second of open ones the scheduler is at
+    pub fn set_current(&self,current:u64){
+        self.reached.fetch_max(current,Ordering::SeqCst);
+        self.update(|status|status.current = current);
+    }
+    //Counts an iteration as in flight, whichever model started it, until the guard is dropped
+    pub fn running(&self)->Running<'_>{
+        let running = self.running.fetch_add(1,Ordering::SeqCst) + 1;
+        self.peak_vus.fetch_max(running,Ordering::SeqCst);
+        Running(self)
+    }
+    pub fn begin_stage(&self,stage:usize,target:u64){
+        let now = self.clock.now();
+        self.close_stage(now);
+        {
+            let mut timeline = self.timeline.lock().unwrap();
+            timeline.started.get_or_insert(now);
+            timeline.current = Option::Some((stage,now));
+        }
+        *self.target.lock().unwrap() = Option::None;
+        self.update(|status|{
+            status.stage = stage;
+            status.target = target;
+            status.state = "running".to_string();
+        });
+    }
+    //All stages are done, in flight iterations are being waited upon
+    pub fn draining(&self){
+        self.close_stage(self.clock.now());
+        self.update(|status|status.state = "stopping".to_string());
+    }
+    pub fn finish(&self){
+        let now = self.clock.now();
+        self.close_stage(now);
+        self.timeline.lock().unwrap().finished.get_or_insert(now);
+        self.update(|status|{
+            status.current = 0;
+            status.state = "finished".to_string();
+        });
+    }
+    fn close_stage(&self,now:SystemTime){
+        let mut timeline = self.timeline.lock().unwrap();
+        if let Some((stage,started)) = timeline.current.take() {
+            timeline.stages.push((stage,now.duration_since(started).unwrap_or_default()));
+        }
+    }
+    pub fn summary(&self)->ScenarioSummary{
+        let status = self.status();
+        let timeline = self.timeline.lock().unwrap();
+        let duration = match (timeline.started,timeline.finished) {
+            (Some(started),Some(finished))=>finished.duration_since(started).unwrap_or_default(),
+            _=>Duration::from_millis(0),
+        };
+        let iterations = self.stats.iterations();
+        ScenarioSummary{
+            scenario:status.scenario,
+            executor:status.executor,
+            iterations,
+            failed:self.stats.failed(),
+            error_rate:self.stats.error_rate(),
+            average_iteration_duration_ms:self.stats.average_duration(),
+            average_corrected_iteration_duration_ms:self.stats.average_corrected_duration(),
+            duration_ms:duration.as_millis(),
+            iterations_per_second:if duration.as_secs_f64() > 0.0 { iterations as f64 / duration.as_secs_f64() } else { 0.0 },
+            target:self.planned.iter().map(|stage|stage.target).max().unwrap_or(0),
+            reached:self.reached.load(Ordering::SeqCst),
+            peak_vus:self.peak_vus.load(Ordering::SeqCst),
+            stages:timeline.stages.iter().map(|(stage,actual)| StageSummary{
+                stage:stage + 1,
+                target:self.planned[*stage].target,
+                planned_ms:self.planned[*stage].duration.as_millis(),
+                actual_ms:actual.as_millis(),
+            }).collect(),
+        }
+    }
+}
+pub struct Running<'a>(&'a ScenarioControl);
+impl Drop for Running<'_>{
+    fn drop(&mut self){
+        self.0.running.fetch_sub(1,Ordering::SeqCst);
+    }
+}
+#[derive(Default)]
+pub struct WorkloadControl{
+    scenarios:RwLock<Vec<Arc<ScenarioControl>>>,
+}
+impl WorkloadControl{
+    pub fn register(&self,control:ScenarioControl)->Arc<ScenarioControl>{
+        let control = Arc::new(control);
+        self.scenarios.write().unwrap().push(control.clone());
+        control
+    }
+    pub fn scenarios(&self,name:Option<&String>)->Vec<Arc<ScenarioControl>>{
+        self.scenarios.read().unwrap().iter()
+            .filter(|sc|name.map(|n|sc.name().eq(n)).unwrap_or(true))
+            .cloned()
+            .collect()
+    }
+}
+pub async fn serve(address:String,control:Arc<WorkloadControl>){
+    let addr = match address.parse() {
+        Ok(addr)=>addr,
+        Err(e)=>{
+            eprintln!("Invalid control address {}: {}",address,e);
+            return;
+        }
+    };
+    let make_svc = make_service_fn(move |_conn|{
+        let control = control.clone();
+        async move { Ok::<_,Infallible>(service_fn(move |req|handle(req,control.clone()))) }
+    });
+    println!("Control api listening on {}",address);
+    if let Err(e) = Server::bind(&addr).serve(make_svc).await {
+        eprintln!("Control api failed: {}",e);
+    }
+}
+async fn handle(req:Request<Body>,control:Arc<WorkloadControl>)->Result<Response<Body>,Infallible>{
+    let query:HashMap<String,String> = req.uri().query()
+        .and_then(|q|serde_urlencoded::from_str(q).ok())
+        .unwrap_or_default();
+    let scenarios = control.scenarios(query.get("scenario"));
+    let response = match (req.method(),req.uri().path()) {
+        (&Method::GET,"/status")=>{
+            let status:Vec<ScenarioStatus> = scenarios.iter().map(|sc|sc.status()).collect();
+            json(StatusCode::OK,serde_json::to_string(&status).unwrap())
+        }
+        (_,_) if scenarios.is_empty()=>json(StatusCode::NOT_FOUND,r#"{"error":"scenario not found"}"#.to_string()),
+        (&Method::POST,"/pause")=>apply(&scenarios,|sc|sc.pause()),
+        (&Method::POST,"/resume")=>apply(&scenarios,|sc|sc.resume()),
+        (&Method::POST,"/next-stage")=>apply(&scenarios,|sc|sc.skip_stage()),
+        (&Method::POST,"/stop")=>apply(&scenarios,|sc|sc.stop()),
+        (&Method::POST,"/scale")=>match query.get("target").and_then(|t|t.parse::<u64>().ok()) {
+            Some(target)=>apply(&scenarios,|sc|sc.scale(target)),
+            None=>json(StatusCode::BAD_REQUEST,r#"{"error":"target should be a positive integer"}"#.to_string()),
+        },
+        _=>json(StatusCode::NOT_FOUND,r#"{"error":"unknown command"}"#.to_string()),
+    };
+    Ok(response)
+}
+fn apply(scenarios:&[Arc<ScenarioControl>],command:impl Fn(&ScenarioControl))->Response<Body>{
+    for sc in scenarios {
+        command(sc);
+    }
+    json(StatusCode::OK,format!(r#"{{"applied":{}}}"#,scenarios.len()))
+}
+fn json(status:StatusCode,body:String)->Response<Body>{
+    Response::builder()
+        .status(status)
+        .header("Content-Type","application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+#[cfg(test)]
+mod tests{
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+    use hyper::{Body, Method, Request, StatusCode};
+    use serde_json::{json, Value};
+    use corr_lib::core::clock::{Clock, VirtualClock};
+    use corr_lib::workload::ModelStage;
+    use crate::runners::control::{handle, ScenarioControl, WorkloadControl};
+
+    fn workload()->(Arc<WorkloadControl>,Arc<ScenarioControl>,Arc<ScenarioControl>){
+        let control = Arc::new(WorkloadControl::default());
+        let stages = vec![ModelStage{target:10,duration:Duration::from_secs(60)},ModelStage{target:0,duration:Duration::from_secs(10)}];
+        let clock:Arc<dyn Clock> = Arc::new(VirtualClock::starting_at(UNIX_EPOCH));
+        let browse = control.register(ScenarioControl::new("Browse".to_string(),"closed",&stages,clock.clone()));
+        let search = control.register(ScenarioControl::new("Search".to_string(),"open",&stages,clock));
+        browse.begin_stage(0,10);
+        browse.set_current(4);
+        (control,browse,search)
+    }
+    async fn call(control:&Arc<WorkloadControl>,method:Method,uri:&str)->(StatusCode,Value){
+        let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        let response = handle(request,control.clone()).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status,serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn should_report_status_of_all_or_named_scenarios(){
+        let (control,_,_) = workload();
+        let (status,body) = call(&control,Method::GET,"/status").await;
+        assert_eq!(status,StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(),2);
+        let (_,body) = call(&control,Method::GET,"/status?scenario=Browse").await;
+        assert_eq!(body,json!([{
+            "scenario":"Browse",
+            "executor":"closed",
+            "stage":0,
+            "stages":2,
+            "target":10,
+            "current":4,
+            "paused":false,
+            "state":"running"
+        }]));
+    }
+    #[tokio::test]
+    async fn should_pause_and_resume_only_named_scenario(){
+        let (control,browse,search) = workload();
+        assert_eq!(call(&control,Method::POST,"/pause?scenario=Search").await,(StatusCode::OK,json!({"applied":1})));
+        assert!(search.is_paused() && !browse.is_paused());
+        let (_,body) = call(&control,Method::GET,"/status?scenario=Search").await;
+        assert_eq!(body[0]["paused"],json!(true));
+        call(&control,Method::POST,"/resume").await;
+        assert!(!search.is_paused());
+    }
+    #[tokio::test]
+    async fn should_scale_skip_stage_and_stop(){
+        let (control,browse,search) = workload();
+        assert_eq!(call(&control,Method::POST,"/scale?scenario=Browse&target=25").await,(StatusCode::OK,json!({"applied":1})));
+        assert_eq!((browse.target_override(),search.target_override()),(Option::Some(25),Option::None));
+        assert_eq!(call(&control,Method::POST,"/scale?target=many").await.0,StatusCode::BAD_REQUEST);
+        call(&control,Method::POST,"/next-stage?scenario=Browse").await;
+        assert!(browse.take_skip_stage() && !search.take_skip_stage());
+        browse.pause();
+        assert_eq!(call(&control,Method::POST,"/stop").await,(StatusCode::OK,json!({"applied":2})));
+        assert!(browse.is_stopping() && search.is_stopping() && !browse.is_paused());
+    }
+    #[test]
+    fn should_report_peak_vus_apart_from_reached_rate(){
+        let (_,_,search) = workload();
+        search.begin_stage(0,10);
+        search.set_current(10);
+        let first = search.running();
+        {
+            let _second = search.running();
+            let _third = search.running();
+        }
+        let _fourth = search.running();
+        drop(first);
+        search.set_current(2);
+        let summary = search.summary();
+        assert_eq!((summary.target,summary.reached,summary.peak_vus),(10,10,3));
+    }
+    #[test]
+    fn should_time_stages_and_run_on_the_clock_of_the_run(){
+        let clock = VirtualClock::starting_at(UNIX_EPOCH);
+        let stages = vec![ModelStage{target:10,duration:Duration::from_secs(60)},ModelStage{target:0,duration:Duration::from_secs(10)}];
+        let control = ScenarioControl::new("Browse".to_string(),"closed",&stages,Arc::new(clock.clone()));
+        control.begin_stage(0,10);
+        clock.advance(Duration::from_secs(60));
+        control.begin_stage(1,0);
+        clock.advance(Duration::from_secs(12));
+        control.draining();
+        clock.advance(Duration::from_secs(3));
+        control.finish();
+        let summary = control.summary();
+        assert_eq!(summary.duration_ms,75000);
+        assert_eq!(summary.stages.iter().map(|stage|stage.actual_ms).collect::<Vec<_>>(),vec![60000,12000]);
+    }
+    #[tokio::test]
+    async fn should_reject_unknown_scenario_and_command(){
+        let (control,browse,_) = workload();
+        assert_eq!(call(&control,Method::POST,"/pause?scenario=Checkout").await,(StatusCode::NOT_FOUND,json!({"error":"scenario not found"})));
+        assert!(!browse.is_paused());
+        assert_eq!(call(&control,Method::POST,"/rewind").await.0,StatusCode::NOT_FOUND);
+        assert_eq!(call(&control,Method::GET,"/status?scenario=Checkout").await,(StatusCode::OK,json!([])));
+    }
+}
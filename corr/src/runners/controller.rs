@@ -0,0 +1,228 @@
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::{Mutex, Notify};
+use corr_lib::core::clock::RealClock;
+use corr_lib::core::scrapper::Scrapper;
+use anyhow::{bail, Result};
+use crate::{Out, RunOptions};
+use crate::runners::agent::{AgentMessage, epoch_millis, send};
+use crate::runners::workload::scrapper_for;
+
+//Agents get this long to receive the start message before they begin together
+const START_DELAY_MILLIS:u64 = 2000;
+struct Agent{
+    address:String,
+    lines:tokio::io::Lines<BufReader<OwnedReadHalf>>,
+    writer:Arc<Mutex<OwnedWriteHalf>>,
+}
+pub struct ControllerRunner;
+impl ControllerRunner{
+    pub async fn run(target:String,workload:String,out:Out,options:RunOptions){
+        if let Err(e) = Self::run_on_agents(target,workload,out,options).await {
+            eprintln!("Runtime Error: {}",e);
+        }
+    }
+    async fn run_on_agents(target:String,workload:String,out:Out,options:RunOptions)->Result<()>{
+        let token = match &options.agent_token {
+            Some(token)=>token.clone(),
+            None=>bail!("Agents need a shared token, pass --agent-token or set CORR_AGENT_TOKEN"),
+        };
+        let package = tokio::fs::read(target.as_str()).await?;
+        let total = options.agents.len();
+        let mut agents = vec![];
+        for (index,address) in options.agents.iter().enumerate() {
+            let stream = TcpStream::connect(address.as_str()).await?;
+            let (reader,mut writer) = stream.into_split();
+            send(&mut writer,&AgentMessage::Prepare{
+                token:token.clone(),
+                workload:workload.clone(),
+                package:package.clone(),
+                index,
+                total,
+                params:options.params.iter().map(|p|(p.name.clone(),p.value.clone())).collect(),
+                seed:options.seed,
+                http:Box::new(options.http.clone()),
+            }).await?;
+            agents.push(Agent{
+                address:address.clone(),
+                lines:BufReader::new(reader).lines(),
+                writer:Arc::new(Mutex::new(writer)),
+            });
+        }
+        for agent in agents.iter_mut() {
+            match Self::reply(agent).await? {
+                AgentMessage::Ready=>println!("Agent {} ready",agent.address),
+                AgentMessage::Error{message}=>bail!("Agent {} failed to prepare: {}",agent.address,message),
+                other=>bail!("Agent {} sent {:?} instead of ready",agent.address,other),
+            }
+        }
+        //Setup runs once, on the first agent, before any agent starts
+        if let Some(first) = agents.first_mut() {
+            send(&mut *first.writer.lock().await,&AgentMessage::Setup).await?;
+            match Self::reply(first).await? {
+                AgentMessage::SetupDone=>println!("Agent {} finished setup",first.address),
+                AgentMessage::Error{message}=>bail!("Agent {} failed to set up: {}",first.address,message),
+                other=>bail!("Agent {} sent {:?} instead of setup done",first.address,other),
+            }
+        }
+        let at = epoch_millis() + START_DELAY_MILLIS;
+        for agent in agents.iter() {
+            send(&mut *agent.writer.lock().await,&AgentMessage::Start{at}).await?;
+        }
+        println!("Starting workload on {} agents",total);
+        let scrapper:Arc<Box<dyn Scrapper>> = Arc::new(scrapper_for(out));
+        let writers:Vec<_> = agents.iter().map(|a|a.writer.clone()).collect();
+        let failed = Arc::new(Notify::new());
+        let collectors:Vec<_> = agents.into_iter().map(|agent|Self::collect(agent,scrapper.clone(),failed.clone())).collect();
+        let all = futures::future::join_all(collectors);
+        tokio::pin!(all);
+        let mut stopping = false;
+        let finished = loop {
+            let reason = tokio::select! {
+                _ = scrapper.start_metrics_loop(Arc::new(RealClock))=>continue,
+                finished = &mut all=>break finished,
+                //Agents that don't answer a stop are given up on when interrupted again
+                _ = tokio::signal::ctrl_c()=>if stopping {
+                    bail!("Interrupted while stopping, disconnected from agents that did not finish")
+                } else {
+                    "Interrupted"
+                },
+                _ = failed.notified(),if !stopping=>"An agent failed",
+            };
+            println!("{}, stopping all agents",reason);
+            for writer in &writers {
+                let _ = send(&mut *writer.lock().await,&AgentMessage::Stop).await;
+            }
+            stopping = true;
+        };
+        println!("All agents finished");
+        //Teardown runs on the first agent once every agent is done, the others are let go
+        let mut finished = finished.into_iter();
+        if let Some((mut first,done)) = finished.next() {
+            drop(finished);
+            if done {
+                send(&mut *first.writer.lock().await,&AgentMessage::Teardown).await?;
+                match Self::reply(&mut first).await? {
+                    AgentMessage::TeardownDone=>println!("Agent {} finished teardown",first.address),
+                    other=>bail!("Agent {} sent {:?} instead of teardown done",first.address,other),
+                }
+            } else {
+                eprintln!("Skipping teardown as agent {} did not finish",first.address);
+            }
+        }
+        Ok(())
+    }
+    async fn reply(agent:&mut Agent)->Result<AgentMessage>{
+        match agent.lines.next_line().await? {
+            Some(line)=>Ok(serde_json::from_str(line.as_str())?),
+            None=>bail!("Agent {} closed the connection",agent.address),
+        }
+    }
+    //Returns the agent with whether it reported being done, failures are signalled as they happen
+    async fn collect(mut agent:Agent,scrapper:Arc<Box<dyn Scrapper>>,failed:Arc<Notify>)->(Agent,bool){
+        while let Ok(Some(line)) = agent.lines.next_line().await {
+            match serde_json::from_str(line.as_str()) {
+                Ok(AgentMessage::Metric{series,data,tags})=>{
+                    let mut tags = tags;
+                    tags.push(("agent".to_string(),agent.address.clone()));
+                    scrapper.ingest(series.as_str(),data,tags).await;
+                }
+                Ok(AgentMessage::Done)=>{
+                    println!("Agent {} finished",agent.address);
+                    return (agent,true);
+                }
+                Ok(AgentMessage::Error{message})=>{
+                    eprintln!("Agent {} failed: {}",agent.address,message);
+                    failed.notify_one();
+                }
+                _=>eprintln!("Unexpected message from agent {}",agent.address),
+            }
+        }
+        eprintln!("Agent {} disconnected",agent.address);
+        failed.notify_one();
+        (agent,false)
+    }
+}
+#[cfg(test)]
+mod tests{
+    use std::convert::Infallible;
+    use std::fs::{create_dir_all, write, File};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use tokio::net::TcpListener;
+    use crate::runners::agent::AgentRunner;
+    use crate::runners::controller::ControllerRunner;
+    use crate::{Out, Param, RunOptions};
+
+    fn can_bind_local_socket()->bool{
+        std::net::TcpListener::bind("127.0.0.1:0").is_ok()
+    }
+    fn package(dir:&Path)->String{
+        let src = dir.join("src");
+        create_dir_all(&src).unwrap();
+        for name in ["Setup","Hit","Cleanup"] {
+            let journey = format!("`{}`(){{\n    get request {{\n        url: text `<%base_url%>/{}`\n    }}\n}}",name,name.to_lowercase());
+            write(src.join(format!("{}.journey",name)),journey).unwrap();
+        }
+        write(src.join("Load.workload"),r#"`Load`(base_url){
+            startup: "Setup",
+            teardown: "Cleanup",
+            scenarios: [{executor:"closed", journey:"Hit", stages:[{2,1}], pacing: 100}]
+        }"#).unwrap();
+        let jpack = dir.join("load.jpack");
+        let mut tar = tar::Builder::new(GzEncoder::new(File::create(&jpack).unwrap(),Compression::default()));
+        tar.append_dir_all("./src",&src).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+        jpack.to_str().unwrap().to_string()
+    }
+    #[tokio::test]
+    async fn should_set_up_once_and_tear_down_after_all_agents_are_done(){
+        if !can_bind_local_socket() {
+            return;
+        }
+        let hits = Arc::new(Mutex::new(Vec::<String>::new()));
+        let recorded = hits.clone();
+        let make_svc = make_service_fn(move |_conn|{
+            let recorded = recorded.clone();
+            async move {
+                Ok::<_,Infallible>(service_fn(move |req:Request<Body>|{
+                    recorded.lock().unwrap().push(req.uri().path().to_string());
+                    async { Ok::<_,Infallible>(Response::new(Body::from("{}"))) }
+                }))
+            }
+        });
+        let server = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://127.0.0.1:{}",server.local_addr().unwrap().port());
+        tokio::spawn(Server::from_tcp(server).unwrap().serve(make_svc));
+        let mut agents = vec![];
+        for _ in 0..2 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            agents.push(listener.local_addr().unwrap().to_string());
+            tokio::spawn(AgentRunner::accept(listener,"secret".to_string()));
+        }
+        let dir = std::env::temp_dir().join(format!("corr-controller-{}",std::process::id()));
+        let options = RunOptions{
+            params:vec![Param{name:"base_url".to_string(),value:base_url}],
+            agents:agents.clone(),
+            agent_token:Option::Some("wrong".to_string()),
+            ..RunOptions::default()
+        };
+        let jpack = package(&dir);
+        assert!(ControllerRunner::run_on_agents(jpack.clone(),"Load".to_string(),Out::Console,options.clone()).await.is_err());
+        let options = RunOptions{agent_token:Option::Some("secret".to_string()),..options};
+        ControllerRunner::run_on_agents(jpack,"Load".to_string(),Out::Console,options).await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        let hits = hits.lock().unwrap().clone();
+        assert_eq!(hits.iter().filter(|path|path.as_str() == "/setup").count(),1);
+        assert_eq!(hits.first().map(String::as_str),Option::Some("/setup"));
+        assert_eq!(hits.iter().filter(|path|path.as_str() == "/cleanup").count(),1);
+        assert_eq!(hits.last().map(String::as_str),Option::Some("/cleanup"));
+        assert!(hits.iter().any(|path|path.as_str() == "/hit"));
+    }
+}
@@ -1,19 +1,18 @@
 use std::env;
-use corr_lib::core::runtime::Context as CorrContext;
 use std::sync::Arc;
 use futures::lock::Mutex;
 use corr_lib::core::scrapper::influxdb2::InfluxDB2Scrapper;
 use corr_lib::core::scrapper::none::NoneScraper;
 use corr_lib::core::scrapper::Scrapper;
-use crate::{client, Out};
+use crate::{client, Out, RunOptions};
 use crate::interfaces::terminal::Terminal;
 pub struct JourneyRunner;
 impl JourneyRunner {
-    pub async fn run(journey_package:String, journey:String, out:Out, debug:bool){
+    pub async fn run(journey_package:String, journey:String, out:Out, options:RunOptions){
         let jp= client::unpack(journey_package).unwrap();
-        Self::run_journey_in(jp,journey,out,debug).await;
+        Self::run_journey_in(jp,journey,out,options).await;
     }
-    pub async fn run_journey_in(jp:String,journey:String,out:Out,debug:bool){
+    pub async fn run_journey_in(jp:String,journey:String,out:Out,options:RunOptions){
         let jrns = client::get_journeis_in(format!("{}/src", jp),"".to_string()).await.unwrap();
         let j = {
 
@@ -27,7 +26,13 @@ impl JourneyRunner {
                 },
                 _=> Box::new(NoneScraper{})
             };
-            let context = CorrContext::new(Arc::new(Mutex::new(terminal.get_if())),jrns,Arc::new(scrapper),debug);
+            let context = match options.context(Arc::new(Mutex::new(terminal.get_if())),jrns,Arc::new(scrapper)) {
+                Ok(context)=>context,
+                Err(e)=>{
+                    eprintln!("Runtime Error: {}",e);
+                    return;
+                }
+            };
             tokio::spawn(async move {
                 client::start(jn.clone(), context).await;
             });
@@ -1,2 +1,6 @@
 pub mod workload;
-pub mod journey;
\ No newline at end of file
+pub mod journey;
+pub mod agent;
+pub mod controller;
+pub mod control;
+pub mod summary;
@@ -0,0 +1,50 @@
+use serde::Serialize;
+use anyhow::Result;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StageSummary{
+    pub stage:usize,
+    pub target:u64,
+    pub planned_ms:u128,
+    pub actual_ms:u128,
+}
+#[derive(Serialize, Debug, Clone)]
+pub struct ScenarioSummary{
+    pub scenario:String,
+    pub executor:String,
+    pub iterations:u64,
+    pub failed:u64,
+    pub error_rate:f64,
+    pub average_iteration_duration_ms:f64,
+    pub average_corrected_iteration_duration_ms:Option<f64>,
+    pub duration_ms:u128,
+    pub iterations_per_second:f64,
+    pub target:u64,
+    pub reached:u64,
+    pub peak_vus:u64,
+    pub stages:Vec<StageSummary>,
+}
+impl ScenarioSummary{
+    //Closed scenarios target VUs while open scenarios target iterations per second
+    fn unit(&self)->&str{
+        if self.executor.eq("open") { "iterations/s" } else { "VUs" }
+    }
+    pub fn print(&self){
+        println!("Summary of test {} ({} model)",self.scenario,self.executor);
+        println!("  iterations: {} total, {} failed, error rate {:.2}",self.iterations,self.failed,self.error_rate);
+        println!("  average iteration duration: {:.2}ms",self.average_iteration_duration_ms);
+        if let Some(corrected) = self.average_corrected_iteration_duration_ms {
+            println!("  average iteration duration from intended start: {:.2}ms",corrected);
+        }
+        println!("  achieved {:.2} iterations/s in {:.2}s",self.iterations_per_second,self.duration_ms as f64 / 1000.0);
+        println!("  target {} {}, reached {} {}",self.target,self.unit(),self.reached,self.unit());
+        println!("  peak {} VUs running iterations at once",self.peak_vus);
+        for stage in &self.stages {
+            println!("  stage {}: target {} {}, planned {:.2}s, ran {:.2}s",stage.stage,stage.target,self.unit(),stage.planned_ms as f64 / 1000.0,stage.actual_ms as f64 / 1000.0);
+        }
+    }
+}
+pub async fn export(path:&str,summaries:&[ScenarioSummary])->Result<()>{
+    tokio::fs::write(path,serde_json::to_string_pretty(summaries)?).await?;
+    Ok(())
+}
@@ -1,229 +1,399 @@
 use std::env;
 use std::sync::Arc;
-use std::time::{Duration};
+use std::time::{Duration, SystemTime};
 use futures::lock::Mutex;
 use core::option::Option;
 use std::collections::HashMap;
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
 use tokio::task::JoinHandle;
-use tokio::time::{Instant, sleep};
-use crate::{client, Out};
+use crate::{client, Out, RunOptions};
 use crate::interfaces::standalone::StandAloneInterface;
+use corr_lib::core::clock::{waiting, Clock};
 use corr_lib::core::runtime::{Context as CorrContext};
 use corr_lib::core::scrapper::influxdb2::InfluxDB2Scrapper;
 use corr_lib::core::scrapper::none::NoneScraper;
 use corr_lib::core::scrapper::{Scrapper};
-use corr_lib::core::Value;
+use corr_lib::core::{convert, DataType, Value};
 
 use corr_lib::journey::{Journey};
 use corr_lib::workload::{ModelScenario, Scenario, WorkLoad};
+use crate::runners::control::{ScenarioControl, WorkloadControl, serve};
+use crate::runners::summary::export;
+//Granularity at which schedulers ramp VUs and pick up control commands
+const SCHEDULER_TICK:Duration = Duration::from_millis(100);
 pub struct WorkLoadRunner;
 impl WorkLoadRunner{
-    pub async fn run(target:String,workload:String,out:Out,debug:bool){
+    pub async fn run(target:String,workload:String,out:Out,options:RunOptions){
         let jp= client::unpack(target).unwrap();
-        Self::run_workload_in(jp, workload,out,debug).await;
+        Self::run_workload_in(jp, workload,out,options).await;
     }
-    pub async fn run_workload_in(jp:String, workload:String,out:Out,debug:bool){
-        let wrklds = client::get_workloads_in(format!("{}/src", jp),"".to_string()).await.unwrap();
-        let jrns = client::get_journeis_in(format!("{}/src", jp),"".to_string()).await.unwrap();
-        let workload = if workload.clone().eq("<default>"){
-            wrklds.get(0).map(|j|j.clone())
-        } else {
-            let mut jn = Option::None;
-            for jrn in &wrklds {
-                if jrn.name.eq(&workload.clone()) {
-                    jn = Option::Some(jrn.clone());
-                    break;
-                }
+    pub async fn run_workload_in(jp:String, workload:String,out:Out,options:RunOptions){
+        if let Some((wl,jrns)) = find_workload(jp,workload).await {
+            schedule_workload(wl,jrns,Arc::new(scrapper_for(out)),options,Arc::new(Notify::new())).await
+        }
+    }
+}
+pub fn scrapper_for(out:Out)->Box<dyn Scrapper>{
+    match out {
+        Out::InfluxDB2=>{
+            Box::new(InfluxDB2Scrapper::new(env::var("J_INFLUX_URL").unwrap().as_str(),env::var("J_INFLUX_TOKEN").unwrap().as_str(),env::var("J_INFLUX_ORG").unwrap().as_str(),env::var("J_INFLUX_BUCKET").unwrap().as_str()))
+        },
+        _=> Box::new(NoneScraper{})
+    }
+}
+pub async fn find_workload(jp:String, workload:String)->Option<(WorkLoad,HashMap<String,Arc<Journey>>)>{
+    let wrklds = client::get_workloads_in(format!("{}/src", jp),"".to_string()).await.unwrap();
+    let jrns = client::get_journeis_in(format!("{}/src", jp),"".to_string()).await.unwrap();
+    let workload = if workload.clone().eq("<default>"){
+        wrklds.get(0).map(|j|j.clone())
+    } else {
+        let mut jn = Option::None;
+        for jrn in &wrklds {
+            if jrn.name.eq(&workload.clone()) {
+                jn = Option::Some(jrn.clone());
+                break;
             }
-            jn
-        };
-        if let Some(wl) = workload {
-                    let scrp:Box<dyn Scrapper> = match out {
-                        Out::InfluxDB2=>{
-                            Box::new(InfluxDB2Scrapper::new(env::var("J_INFLUX_URL").unwrap().as_str(),env::var("J_INFLUX_TOKEN").unwrap().as_str(),env::var("J_INFLUX_ORG").unwrap().as_str(),env::var("J_INFLUX_BUCKET").unwrap().as_str()))
-                        },
-                        _=> Box::new(NoneScraper{})
-                    };
-            schedule_workload(wl,jrns,Arc::new(scrp),debug).await
+        }
+        jn
+    };
+    workload.map(|wl|(wl,jrns))
+}
+pub async fn schedule_workload(workload:WorkLoad, journeys:HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, options:RunOptions, stop:Arc<Notify>){
+    if let Some(context) = prepare_workload(&workload,journeys.clone(),scrapper.clone(),&options).await {
+        if setup_workload(&workload,&journeys,&context).await && run_scenarios(&workload,&journeys,scrapper,&options,&context,stop).await {
+            teardown_workload(&workload,&journeys,&context).await;
         }
     }
 }
-pub async fn schedule_workload(workload:WorkLoad, journeys:HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, debug:bool){
-    let context = CorrContext::new(Arc::new(Mutex::new(StandAloneInterface{})),journeys.clone(),scrapper.clone(),debug);
+//Context with run options applied and workload parameters defined, None when any of them is invalid
+pub async fn prepare_workload(workload:&WorkLoad, journeys:HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, options:&RunOptions)->Option<CorrContext>{
+    let params = options.params.clone();
+    let context = match options.context(Arc::new(Mutex::new(StandAloneInterface{})),journeys.clone(),scrapper) {
+        Ok(context)=>context,
+        Err(e)=>{
+            eprintln!("Runtime Error: {}",e);
+            return Option::None;
+        }
+    };
     let mut cont = true;
+    for param in &workload.params {
+        let supplied = params.iter().find(|p|p.name.eq(&param.name));
+        let value = supplied.and_then(|p|convert(param.name.clone(),p.value.clone(),param.data_type.unwrap_or(DataType::String)));
+        if let Some(vv) = value {
+            context.define(vv.name,vv.value).await;
+        } else {
+            cont = false;
+            eprintln!("Runtime Error: Workload parameter {} is missing or invalid, pass it as --param {}=<value>",param.name,param.name);
+        }
+    }
+    for param in &params {
+        if !workload.params.iter().any(|p|p.name.eq(&param.name)) {
+            eprintln!("Warning: Parameter {} is not declared by workload {}",param.name,workload.name);
+        }
+    }
+    for scenario in &workload.scenarios {
+        let (Scenario::Closed(ms) | Scenario::Open(ms)) = scenario;
+        if let Err(e) = ms.check_args(&journeys) {
+            cont = false;
+            eprintln!("Runtime Error: {}",e);
+        }
+    }
+    if cont { Option::Some(context) } else { Option::None }
+}
+//Runs setup journey of the workload if it has one, false when it is not found
+pub async fn setup_workload(workload:&WorkLoad, journeys:&HashMap<String,Arc<Journey>>, context:&CorrContext)->bool{
     if let Some(setup) = &workload.setup{
         if let Some(jn) = journeys.get(setup) {
             client::start(jn.clone(), context.clone()).await;
         } else {
-            cont = false;
             eprintln!("Runtime Error: Setup Journey {} not found exiting execution",setup);
+            return false;
         }
-
     }
-    if cont {
-        let joins:Vec<_> = workload.scenarios.iter().map(|sc|sc.clone()).map(|sc|schedule_scenario(sc, journeys.clone(), scrapper.clone(), context.clone(), debug)).collect();
-        tokio::select! {
-            _= scrapper.start_metrics_loop()=>{},
-            _= futures::future::join_all(joins)=>{}
+    true
+}
+//Runs all scenarios and reports their summaries, false when the workload could not be started
+pub async fn run_scenarios(workload:&WorkLoad, journeys:&HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, options:&RunOptions, context:&CorrContext, stop:Arc<Notify>)->bool{
+    let debug = options.debug;
+    let mut cont = true;
+    for feeder in &workload.feeders {
+        if let Err(e) = context.feeder_store.declare(feeder).await {
+            cont = false;
+            eprintln!("Runtime Error: Unable to load feeder {} - {}",feeder.path,e);
+        }
+    }
+    if !cont {
+        return false;
+    }
+    let control = Arc::new(WorkloadControl::default());
+    let api = options.control.clone().map(|address|tokio::spawn(serve(address,control.clone())));
+    let joins:Vec<_> = workload.scenarios.iter().map(|sc|sc.clone()).map(|sc|schedule_scenario(sc, journeys.clone(), scrapper.clone(), context.clone(), debug, control.clone())).collect();
+    tokio::select! {
+        _= context.clock.work().run(scrapper.start_metrics_loop(context.clock.clone()))=>{},
+        _= context.clock.work().run(futures::future::join_all(joins))=>{},
+        _= tokio::signal::ctrl_c()=>{println!("Interrupted, stopping all scenarios")},
+        _= stop.notified()=>{println!("Stopped by controller, stopping all scenarios")}
+    }
+    if let Some(api) = api {
+        api.abort();
+    }
+    let summaries:Vec<_> = control.scenarios(Option::None).iter().map(|sc|{
+        sc.finish();
+        sc.summary()
+    }).collect();
+    for summary in &summaries {
+        summary.print();
+    }
+    if let Some(path) = &options.summary {
+        if let Err(e) = export(path,&summaries).await {
+            eprintln!("Runtime Error: Unable to write summary to {} - {}",path,e);
         }
     }
+    true
 }
-async fn schedule_scenario(scenario:Scenario, journeys:HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, context:CorrContext, debug:bool){
+pub async fn teardown_workload(workload:&WorkLoad, journeys:&HashMap<String,Arc<Journey>>, context:&CorrContext){
+    if let Some(teardown) = &workload.teardown{
+        if let Some(jn) = journeys.get(teardown) {
+            client::start(jn.clone(), context.clone()).await;
+        } else {
+            eprintln!("Runtime Error: Teardown Journey {} not found",teardown);
+        }
+    }
+}
+struct InFlight(Vec<JoinHandle<()>>);
+impl InFlight {
+    async fn stop(mut self,journey:String,graceful_stop:Option<Duration>,clock:Arc<dyn Clock>){
+        let all = waiting(futures::future::join_all(self.0.iter_mut()));
+        if let Some(gs) = graceful_stop {
+            tokio::select! {
+                _=clock.sleep(gs)=>{println!("Forcefully stopped {}",journey)},
+                _=all=>{println!("Normally stopped {}",journey)}
+            }
+        } else {
+            all.await;
+            println!("Normally stopped {}",journey)
+        }
+    }
+}
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        for th in &self.0 {
+            th.abort();
+        }
+    }
+}
+async fn schedule_scenario(scenario:Scenario, journeys:HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, context:CorrContext, debug:bool, workload_control:Arc<WorkloadControl>){
     let dist = env::var("J_WORKERS").unwrap_or("1".into()).as_str().parse().unwrap_or(1.0);
     let count = Arc::new(RwLock::new(0.0));
     let vu_count = Arc::new(RwLock::new(0.0));
     let cc = count.clone();
     let vuc = vu_count.clone();
     let scpr = scrapper.clone();
+    let (start_time,abort_on_error_rate,name,executor,stages) = match &scenario {
+        Scenario::Closed(ms)=>(ms.start_time,ms.abort_on_error_rate,ms.name(),"closed",ms.stages.clone()),
+        Scenario::Open(ms)=>(ms.start_time,ms.abort_on_error_rate,ms.name(),"open",ms.stages.clone())
+    };
+    let control = workload_control.register(ScenarioControl::new(name.clone(),executor,&stages,context.clock.clone()));
+    if let Some(st) = start_time {
+        if !debug {
+            context.clock.sleep(st).await;
+        }
+    }
+    let counters_clock = context.clock.clone();
     let counters_scrapper = async move |jn:String|{
         loop {
-            let mut ct = count.write().await;
-            let vuct = vuc.read().await;
-            scpr.ingest("iteration_count",*ct,vec![("journey".to_string(),jn.clone())]).await;
-            scpr.ingest("vus",dist*(*vuct),vec![("jounrey".to_string(),jn.clone())]).await;
-            *ct = 0.0;
-            sleep(Duration::from_millis(100)).await
+            //Let go of the counters before sleeping, iterations wait on them to count themselves
+            {
+                let mut ct = count.write().await;
+                let vuct = vuc.read().await;
+                scpr.ingest("iteration_count",*ct,vec![("journey".to_string(),jn.clone())]).await;
+                scpr.ingest("vus",dist*(*vuct),vec![("jounrey".to_string(),jn.clone())]).await;
+                *ct = 0.0;
+            }
+            counters_clock.sleep(Duration::from_millis(100)).await
+        }
+    };
+    let watched = control.clone();
+    let watcher_clock = context.clock.clone();
+    let error_rate_watcher = async move |jn:String|{
+        if let Some(rate) = abort_on_error_rate {
+            loop {
+                watcher_clock.sleep(Duration::from_secs(1)).await;
+                if watched.stats.exceeds(rate) {
+                    eprintln!("Aborting test {} as error rate {:.2} exceeded {}",jn,watched.stats.error_rate(),rate);
+                    break;
+                }
+            }
+        } else {
+            futures::future::pending::<()>().await
         }
     };
     match scenario {
         Scenario::Closed(cms)=>{
-            let jn = cms.journey.clone();
             tokio::select! {
-                _=closed_model_scenario_scheduler(cms,journeys,scrapper,cc,vu_count,context.clone(),debug)=>{},
-                _=counters_scrapper(jn)=>{},
+                _=closed_model_scenario_scheduler(cms,journeys,scrapper,cc,vu_count,control.clone(),context.clone(),debug)=>{},
+                _=counters_scrapper(name.clone())=>{},
+                _=error_rate_watcher(name.clone())=>{},
             };
         },
         Scenario::Open(oms)=>{
-            let jn = oms.journey.clone();
             tokio::select! {
-                _=open_model_scenario_scheduler(oms,journeys,scrapper,cc,context.clone(),debug)=>{},
-                _=counters_scrapper(jn)=>{},
+                _=open_model_scenario_scheduler(oms,journeys,scrapper,cc,control.clone(),context.clone(),debug)=>{},
+                _=counters_scrapper(name.clone())=>{},
+                _=error_rate_watcher(name.clone())=>{},
             };
         }
     }
+    control.finish();
 }
-async fn open_model_scenario_scheduler(scenario:ModelScenario, journeys:HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, ic:Arc<RwLock<f64>>, context:CorrContext, debug:bool) {
+async fn open_model_scenario_scheduler(scenario:ModelScenario, journeys:HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, ic:Arc<RwLock<f64>>, control:Arc<ScenarioControl>, context:CorrContext, debug:bool) {
+    let scenario = Arc::new(scenario);
     if debug {
-        start_iteration(scenario.journey.clone(),journeys.clone(),scrapper.clone(),ic.clone(),context.clone()).await.await.unwrap();
+        waiting(start_iteration(0,context.clock.now(),scenario.clone(),journeys.clone(),scrapper.clone(),ic.clone(),control.clone(),context.clone()).await).await.unwrap();
     } else {
         let stages= scenario.stages.clone();
-        let mut threads = vec![];
-        let _vu =0;
-        let mut prev = 0;
+        let mut threads = InFlight(vec![]);
+        let mut iteration = 0;
         let mut last_stage = 0;
-        for stage in stages{
-            for j in 0..stage.duration {
-                if stage.target > prev {
-                    prev = last_stage + ((j+1) as f64*((stage.target - last_stage) as f64 / stage.duration as f64)) as u64;
-                } else {
-                    prev = last_stage - ((j+1) as f64 *(( last_stage - stage.target) as f64 / stage.duration as f64)) as u64;
+        //When each batch should have been started, falls behind the clock if the scheduler stalls
+        let mut intended = context.clock.now();
+        for (index,stage) in stages.iter().enumerate(){
+            if control.is_stopping() {
+                break;
+            }
+            control.begin_stage(index,stage.target);
+            let stage_secs = stage.duration.as_secs();
+            let mut j = 0;
+            while j < stage_secs {
+                if control.is_stopping() || control.take_skip_stage() {
+                    break;
+                }
+                if control.is_paused() {
+                    control.set_current(0);
+                    context.clock.sleep(SCHEDULER_TICK).await;
+                    intended = context.clock.now();
+                    continue;
                 }
-                if prev!=0{
-                    let nowo = Instant::now();
-                    for _i in 0..(prev){
-                        let th=start_iteration(scenario.journey.clone(),journeys.clone(),scrapper.clone(),ic.clone(),context.clone()).await;
-                        threads.push(th);
+                let rate = if let Some(target) = control.target_override() {
+                    target
+                } else if stage.target > last_stage {
+                    last_stage + ((j+1) as f64*((stage.target - last_stage) as f64 / stage_secs as f64)) as u64
+                } else {
+                    last_stage - ((j+1) as f64 *(( last_stage - stage.target) as f64 / stage_secs as f64)) as u64
+                };
+                control.set_current(rate);
+                if rate!=0{
+                    let nowo = context.clock.now();
+                    for _i in 0..rate{
+                        let th=start_iteration(iteration,intended,scenario.clone(),journeys.clone(),scrapper.clone(),ic.clone(),control.clone(),context.clone()).await;
+                        threads.0.push(th);
+                        iteration += 1;
                     }
-                    let elo = nowo.elapsed().as_micros() as u64;
+                    let elo = context.clock.now().duration_since(nowo).unwrap_or(Duration::from_micros(0)).as_micros() as u64;
                     if elo < 1000000 {
-                        sleep(Duration::from_micros(1000000-elo)).await;
+                        context.clock.sleep(Duration::from_micros(1000000-elo)).await;
                     }
                 } else {
-                    sleep(Duration::from_millis(1000)).await;
+                    context.clock.sleep(Duration::from_millis(1000)).await;
                 }
-
+                intended += Duration::from_secs(1);
+                j += 1;
             }
             last_stage = stage.target;
         }
-        if let Some(ft) = &scenario.force_stop {
-            tokio::select! {
-            _=sleep(Duration::from_secs(ft.clone()))=>{println!("Forcefully stopped {}",scenario.journey)},
-            _=futures::future::join_all(threads)=>{println!("Normally stopped {}",scenario.journey)}
-        }
-        } else {
-            futures::future::join_all(threads).await;
-            println!("Normally stopped {}",scenario.journey)
-        }
+        control.draining();
+        threads.stop(scenario.name(),scenario.graceful_stop.or(scenario.force_stop),context.clock.clone()).await;
     }
 
 }
-async fn closed_model_scenario_scheduler(scenario:ModelScenario, journeys:HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, ic:Arc<RwLock<f64>>,vu_count:Arc<RwLock<f64>>, context:CorrContext, debug:bool) {
+async fn closed_model_scenario_scheduler(scenario:ModelScenario, journeys:HashMap<String,Arc<Journey>>, scrapper:Arc<Box<dyn Scrapper>>, ic:Arc<RwLock<f64>>,vu_count:Arc<RwLock<f64>>, control:Arc<ScenarioControl>, context:CorrContext, debug:bool) {
+    let scenario = Arc::new(scenario);
 
     let stages= scenario.stages.clone();
     let mut vus = vec![];
-    let mut threads = vec![];
-    let mut prev_num:i64 = 0;
+    let mut threads = InFlight(vec![]);
     let mut vu =0;
-    let _vcc = vu_count.clone();
-    let _scc = scrapper.clone();
-    let _jnn = scenario.journey.clone();
-    let _jnnc = scenario.journey.clone();
     if debug {
-        start_iteration(scenario.journey.clone(),journeys.clone(),scrapper.clone(),ic.clone(),context.clone()).await.await.unwrap();
+        waiting(start_iteration(0,context.clock.now(),scenario.clone(),journeys.clone(),scrapper.clone(),ic.clone(),control.clone(),context.clone()).await).await.unwrap();
     } else {
-        for stage in stages{
-            let delta = (stage.target as i64) - prev_num;
-            if delta >= 0 {
-                println!("Ramping up {} VUs in {} seconds for test {}",delta,stage.duration,scenario.journey.clone());
-                if delta!=0{
-                    let delay = stage.duration * 1000 / (delta  as u64);
-                    for _i in 0..delta{
-                        let (vuh,th)=start_vu(vu,scenario.journey.clone(),journeys.clone(),scrapper.clone(),vu_count.clone(),ic.clone(),context.clone()).await;
-                        vus.push(vuh);
-                        threads.push(th);
-                        sleep(Duration::from_millis(delay)).await;
-                        vu = vu + 1;
-                        let _count = vu_count.read().await;
-                        // scrapper.ingest("vus",*count,vec![("jounrey".to_string(),jnn.clone())]).await;
-                    }
+        if scenario.correct_latency && scenario.pacing.is_none() {
+            eprintln!("Warning: correctLatency needs pacing for closed test {}, latency will not be corrected",scenario.name());
+        }
+        let mut from = 0;
+        for (index,stage) in stages.iter().enumerate(){
+            if control.is_stopping() {
+                break;
+            }
+            control.begin_stage(index,stage.target);
+            if stage.target >= from {
+                println!("Ramping up {} VUs in {:?} for test {}",stage.target - from,stage.duration,scenario.name());
+            } else {
+                println!("Ramping down {} VUs in {:?} for test {}",from - stage.target,stage.duration,scenario.name());
+            }
+            let mut elapsed = Duration::from_millis(0);
+            loop {
+                if control.is_stopping() || control.take_skip_stage() {
+                    break;
                 }
-                else {
-                    let mut st=0;
-                    while st<stage.duration {
-                        let _count = vu_count.read().await;
-                        // scrapper.ingest("vus",*count,vec![("jounrey".to_string(),jnn.clone())]).await;
-                        sleep(Duration::from_secs(1)).await;
-                        st =st +1;
-                    }
+                if control.is_paused() {
+                    context.clock.sleep(SCHEDULER_TICK).await;
+                    continue;
                 }
-            } else {
-                println!("Ramping down {} VUs in {} seconds for test {}",delta*-1,stage.duration,scenario.journey.clone());
-                let delay = stage.duration * 1000 / ((delta * -1) as u64);
-                for _i in 0..(delta*-1){
+                let target = control.target_override().unwrap_or_else(||ramp(from,stage.target,elapsed,stage.duration));
+                while (vus.len() as u64) < target {
+                    let (vuh,th)=start_vu(vu,scenario.clone(),journeys.clone(),scrapper.clone(),vu_count.clone(),ic.clone(),control.clone(),context.clone()).await;
+                    vus.push(vuh);
+                    threads.0.push(th);
+                    vu = vu + 1;
+                }
+                while (vus.len() as u64) > target {
                     if let Some(vuc) = vus.pop(){
                         let _ = vuc.send(1);
                         vu=vu-1;
                     }
-                    sleep(Duration::from_millis(delay)).await;
-                    let _count = vu_count.read().await;
-                    // scrapper.ingest("vus",*count,vec![("jounrey".to_string(),jnn.clone())]).await;
                 }
-
+                control.set_current(vus.len() as u64);
+                if elapsed >= stage.duration {
+                    break;
+                }
+                let tick = SCHEDULER_TICK.min(stage.duration - elapsed);
+                context.clock.sleep(tick).await;
+                elapsed += tick;
             }
-            prev_num = stage.target as i64;
+            from = vus.len() as u64;
         }
         for _i  in 0..vus.len(){
             if let Some(vu) = vus.pop(){
                 let _ = vu.send(1);
             }
         }
-        if let Some(ft) = &scenario.force_stop {
-            tokio::select! {
-            _=sleep(Duration::from_secs(ft.clone()))=>{println!("Forcefully stopped {}",scenario.journey)},
-            _=futures::future::join_all(threads)=>{println!("Normally stopped {}",scenario.journey)}
-        }
-        } else {
-            futures::future::join_all(threads).await;
-            println!("Normally stopped {}",scenario.journey)
-        }
+        control.draining();
+        threads.stop(scenario.name(),scenario.graceful_stop.or(scenario.force_stop),context.clock.clone()).await;
     }
 
 }
-async fn start_vu(number:u64,name:String,journeys:HashMap<String,Arc<Journey>>,scrapper:Arc<Box<dyn Scrapper>>,vu_count:Arc<RwLock<f64>>,ic:Arc<RwLock<f64>>,context:CorrContext)->(tokio::sync::mpsc::UnboundedSender<u64>,JoinHandle<()>){
+//Number of VUs a stage ramping from one target to another should have after elapsed time, VUs start and stop evenly spread over the stage
+fn ramp(from:u64,to:u64,elapsed:Duration,duration:Duration)->u64{
+    if elapsed >= duration || from == to {
+        return to;
+    }
+    let fraction = elapsed.as_secs_f64() / duration.as_secs_f64();
+    let delta = to.abs_diff(from);
+    let step = ((delta as f64 * fraction).floor() as u64 + 1).min(delta);
+    if to > from {
+        from + step
+    } else {
+        from - step
+    }
+}
+async fn start_vu(number:u64,scenario:Arc<ModelScenario>,journeys:HashMap<String,Arc<Journey>>,scrapper:Arc<Box<dyn Scrapper>>,vu_count:Arc<RwLock<f64>>,ic:Arc<RwLock<f64>>,control:Arc<ScenarioControl>,context:CorrContext)->(tokio::sync::mpsc::UnboundedSender<u64>,JoinHandle<()>){
+    let mut context = CorrContext::copy_from(&context).await;
+    context.http = context.http.for_vu(scenario.name().as_str());
     context.define("__VU".into(),Value::PositiveInteger(number.clone() as u128)).await;
     let (tx,mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let work = context.clock.work();
     let flag = Arc::new(RwLock::new(true));
+    let name = scenario.name();
     let name_clone=name.clone();
     let mut setter = async move |checker:Arc<RwLock<bool>>|{
         if let Some(_val)=rx.recv().await{
@@ -242,20 +412,60 @@ async fn start_vu(number:u64,name:String,journeys:HashMap<String,Arc<Journey>>,s
         }
         let mut total_resp = 0;
         let mut intc:f64 = 0.0;
+        //With pacing every iteration is due one pacing after the previous one was due, however long that one took
+        let correction = scenario.pacing.filter(|_|scenario.correct_latency);
+        let mut intended = context.clock.now();
         loop {
+            if control.is_paused() {
+                while control.is_paused() {
+                    context.clock.sleep(SCHEDULER_TICK).await;
+                }
+                intended = context.clock.now();
+            }
             context.define("__ITER".into(),Value::PositiveInteger(iteration.clone())).await;
+            context.reseed().await;
             let flg = checker.read().await;
             if *flg {
-                let resp = test(name.clone(),journeys.clone(),scrapper.clone(),context.clone()).await;
+                let journey = scenario.pick_journey(&context);
+                let started = context.clock.now();
+                let (resp,success) = {
+                    let _running = control.running();
+                    if let Some(result) = test(journey.clone(),scenario.clone(),journeys.clone(),scrapper.clone(),context.clone()).await {
+                        result
+                    } else {
+                        println!("Feeders exhausted, stopping VU {} for test {}",number, name.clone());
+                        break;
+                    }
+                };
                 total_resp = total_resp + resp;
                 intc = intc+1.0;
-                scrapper.ingest("iteration_duration",resp as f64,vec![("journey".to_string(),name.clone())]).await;
+                control.stats.record(success,resp);
+                if !success {
+                    scrapper.ingest("iterations_failed",1.0,vec![("journey".to_string(),journey.clone())]).await;
+                }
+                if let Some(pacing) = correction {
+                    let corrected = resp + started.duration_since(intended).unwrap_or(Duration::from_millis(0)).as_millis();
+                    control.stats.record_corrected(corrected);
+                    scrapper.ingest("corrected_iteration_duration",corrected as f64,vec![("journey".to_string(),journey.clone())]).await;
+                    intended += pacing;
+                }
+                scrapper.ingest("iteration_duration",resp as f64,vec![("journey".to_string(),journey)]).await;
                 if total_resp >= 500 {
                     let mut ic_ref = ic.write().await;
                     *ic_ref = *ic_ref + intc;
                     intc = 0.0;
                     total_resp = 0;
                 }
+                if let Some(tt) = &scenario.think_time {
+                    context.clock.sleep(context.with_rng(|rng|tt.sample(rng))).await;
+                }
+                //When correcting, iterations that fell behind start right away to catch up with the schedule
+                let due = if correction.is_some() { Option::Some(intended) } else { scenario.pacing.map(|pacing|started + pacing) };
+                if let Some(due) = due {
+                    if let Ok(wait) = due.duration_since(context.clock.now()) {
+                        context.clock.sleep(wait).await;
+                    }
+                }
                 iteration = iteration+1;
             } else {
                 println!("Stopping VU {} for test {}",number, name.clone());
@@ -268,25 +478,63 @@ async fn start_vu(number:u64,name:String,journeys:HashMap<String,Arc<Journey>>,s
         }
     };
     let flag1 = flag.clone();
-    let h=tokio::spawn(async move {
+    let h=work.spawn(async move {
         tokio::join!(setter(flag.clone()),vu_loop(flag1));
     });
     (tx,h)
 }
-async fn start_iteration(name:String,journeys:HashMap<String,Arc<Journey>>,scrapper:Arc<Box<dyn Scrapper>>,ic:Arc<RwLock<f64>>,context:CorrContext)->JoinHandle<()>{
+async fn start_iteration(iteration:u64,intended:SystemTime,scenario:Arc<ModelScenario>,journeys:HashMap<String,Arc<Journey>>,scrapper:Arc<Box<dyn Scrapper>>,ic:Arc<RwLock<f64>>,control:Arc<ScenarioControl>,context:CorrContext)->JoinHandle<()>{
+    let work = context.clock.work();
     let cc = async move ||{
-        let resp = test(name.clone(),journeys,scrapper.clone(),context.clone()).await;
-        scrapper.ingest("iteration_duration",resp as f64,vec![("journey".to_string(),name.clone())]).await;
+        let mut context = CorrContext::copy_from(&context).await;
+        context.http = context.http.for_vu(scenario.name().as_str());
+        context.define("__ITER".into(),Value::PositiveInteger(iteration as u128)).await;
+        context.reseed().await;
+        let journey = scenario.pick_journey(&context);
+        let started = context.clock.now();
+        let running = control.running();
+        let (resp,success) = if let Some(result) = test(journey.clone(),scenario.clone(),journeys,scrapper.clone(),context.clone()).await {
+            result
+        } else {
+            //Stops the scheduler instead of skipping every iteration still to come
+            if !control.is_stopping() {
+                eprintln!("Feeders exhausted, stopping test {}",scenario.name());
+            }
+            control.stop();
+            return;
+        };
+        drop(running);
+        let corrected = resp + started.duration_since(intended).unwrap_or(Duration::from_millis(0)).as_millis();
+        control.stats.record(success,resp);
+        control.stats.record_corrected(corrected);
+        if !success {
+            scrapper.ingest("iterations_failed",1.0,vec![("journey".to_string(),journey.clone())]).await;
+        }
+        scrapper.ingest("corrected_iteration_duration",corrected as f64,vec![("journey".to_string(),journey.clone())]).await;
+        scrapper.ingest("iteration_duration",resp as f64,vec![("journey".to_string(),journey)]).await;
         {
             let mut ic_ref = ic.write().await;
             *ic_ref = *ic_ref + 1.0;
         }
     };
-    tokio::spawn(cc())
+    work.spawn(cc())
 }
-async fn test(name:String,journeys:HashMap<String,Arc<Journey>>,_scrapper:Arc<Box<dyn Scrapper>>,context:CorrContext)->u128{
-    let context = CorrContext::copy_from(&context).await;//CorrContext::new(Arc::new(Mutex::new(StandAloneInterface{})),journeys.clone(),scrapper.clone());
-    let now = Instant::now();
-    client::start(journeys.get(&name).unwrap().clone(), context).await;
-    now.elapsed().as_millis()
+async fn test(name:String,scenario:Arc<ModelScenario>,journeys:HashMap<String,Arc<Journey>>,_scrapper:Arc<Box<dyn Scrapper>>,context:CorrContext)->Option<(u128,bool)>{
+    let mut context = CorrContext::copy_from(&context).await;//CorrContext::new(Arc::new(Mutex::new(StandAloneInterface{})),journeys.clone(),scrapper.clone());
+    context.start_iteration();
+    context.reseed().await;
+    if !context.feeder_store.feed(&context).await {
+        return Option::None;
+    }
+    //Measured on the context clock, same as intended and started times it gets corrected against
+    let clock = context.clock.clone();
+    let now = clock.now();
+    let elapsed = ||clock.now().duration_since(now).unwrap_or(Duration::from_millis(0)).as_millis();
+    let journey = journeys.get(&name).unwrap().clone();
+    if let Err(e) = scenario.bind_args(&journey,&context).await {
+        eprintln!("Error {} while evaluating arguments for journey {}",e,name);
+        return Option::Some((elapsed(),false));
+    }
+    let success = client::start(journey, context).await;
+    Option::Some((elapsed(),success))
 }
\ No newline at end of file